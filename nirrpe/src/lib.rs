@@ -0,0 +1,262 @@
+//! # 🗺️ Nirrpe
+//!
+//! Scripts are compiled with an [`Engine`] and then run on a [`Runtime`],
+//! which keeps the modules they import and the globals they share:
+//!
+//! ```no_run
+//! use nirrpe::{Engine, Runtime, Value};
+//!
+//! let script = Engine::new().compile("\"hello {name}\"", "hello.nir").unwrap();
+//! let mut runtime = Runtime::new();
+//! runtime.set_global("name", Value::Str("world".to_string()));
+//! let greeting = script.run(&mut runtime).unwrap();
+//! assert_eq!(greeting.to_string(), "hello world");
+//! ```
+
+#![feature(type_alias_impl_trait)]
+#![feature(int_roundings)]
+#![feature(decl_macro)]
+#![feature(adt_const_params)]
+#![feature(let_chains)]
+#![allow(incomplete_features)]
+#![allow(clippy::type_complexity)]
+
+use std::fmt::{Debug, Display, Formatter};
+use std::iter;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use ariadne::{Color, Label, Report, ReportKind};
+use chumsky::error::Rich;
+use chumsky::input::Input;
+use chumsky::prelude::SimpleSpan;
+use chumsky::Parser;
+
+use crate::parse::ast::Program;
+pub use crate::runtime::loader::{FileLoader, ModuleLoader};
+pub use crate::runtime::natives::{FromValue, IntoValue};
+pub use crate::runtime::trace::Panic as RuntimeError;
+use crate::runtime::trace::{FrameKind, Location};
+pub use crate::runtime::value::Value;
+pub use crate::runtime::NirrpeRuntime as Runtime;
+
+pub mod parse;
+pub mod runtime;
+pub mod typeck;
+
+/// Compiles source files into [`Script`]s.
+#[derive(Default)]
+pub struct Engine {
+    loader: Option<Rc<dyn ModuleLoader>>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an engine whose scripts import modules with `loader`, rather
+    /// than from files in the directory of the main script.
+    pub fn with_loader(loader: impl ModuleLoader + 'static) -> Self {
+        Self {
+            loader: Some(Rc::new(loader)),
+        }
+    }
+
+    /// Lexes, parses and checks a source file, returning every error found
+    /// in the first stage that failed.
+    pub fn compile(&self, source: &str, filename: &str) -> Result<Script, Diagnostics> {
+        let diagnostics = |errors| Diagnostics {
+            filename: filename.to_string(),
+            source: source.to_string(),
+            errors,
+        };
+
+        let (tokens, errs) = parse::lexer::lexer().parse(source).into_output_errors();
+        if !errs.is_empty() {
+            return Err(diagnostics(errs.into_iter().map(Diagnostic::from).collect()));
+        }
+        let Some(tokens) = tokens else {
+            return Err(diagnostics(Vec::new()));
+        };
+        if let Err(err) = parse::check_nesting(&tokens) {
+            return Err(diagnostics(vec![Diagnostic::from(err)]));
+        }
+
+        let (program, errs) = parse::parser()
+            .parse(tokens.as_slice().spanned((source.len()..source.len()).into()))
+            .into_output_errors();
+        if !errs.is_empty() {
+            return Err(diagnostics(errs.into_iter().map(Diagnostic::from).collect()));
+        }
+        let Some(program) = program else {
+            return Err(diagnostics(Vec::new()));
+        };
+
+        let mut errs = typeck::check(&program);
+        errs.extend(typeck::effects::check(&program));
+        if !errs.is_empty() {
+            return Err(diagnostics(
+                errs.into_iter()
+                    .map(|x| Diagnostic::from(Rich::<char>::custom(x.span, x.message)))
+                    .collect(),
+            ));
+        }
+
+        let path = PathBuf::from(filename);
+        let loader = match &self.loader {
+            Some(x) => x.clone(),
+            None => Rc::new(FileLoader::beside(&path)),
+        };
+        Ok(Script {
+            path,
+            source: source.to_string(),
+            program,
+            loader,
+        })
+    }
+}
+
+/// A compiled source file, ready to be run.
+#[derive(Clone)]
+pub struct Script {
+    /// Where the script was loaded from, which modules it imports are
+    /// resolved relative to.
+    pub path: PathBuf,
+    /// The text the script was compiled from, which panic reports quote.
+    pub source: String,
+    pub program: Program,
+    /// Finds the modules the script imports.
+    pub loader: Rc<dyn ModuleLoader>,
+}
+
+impl Debug for Script {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Script")
+            .field("path", &self.path)
+            .field("program", &self.program)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Script {
+    /// Runs this script as the main module, returning the value of its last statement.
+    pub fn run(&self, runtime: &mut Runtime) -> Result<Value, RuntimeError> {
+        runtime.execute(self)
+    }
+}
+
+/// The errors found while compiling a source file.
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    pub filename: String,
+    pub source: String,
+    pub errors: Vec<Diagnostic>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub span: SimpleSpan,
+    pub message: String,
+    /// Why the code at `span` is wrong.
+    pub reason: String,
+    /// What was being parsed when the error happened.
+    pub contexts: Vec<(String, SimpleSpan)>,
+}
+
+impl<T, L> From<Rich<'_, T, SimpleSpan, L>> for Diagnostic
+where
+    T: Debug + Clone,
+    L: Display + ToString + Clone,
+{
+    fn from(err: Rich<'_, T, SimpleSpan, L>) -> Self {
+        let err = err.map_token(|c| format!("{:?}", c));
+        Self {
+            span: *err.span(),
+            message: err.to_string(),
+            reason: err.reason().to_string(),
+            contexts: err.contexts().map(|(label, span)| (label.to_string(), *span)).collect(),
+        }
+    }
+}
+
+impl Diagnostics {
+    /// Prints a report for each error to stderr.
+    pub fn eprint(&self) {
+        for err in &self.errors {
+            Report::build(ReportKind::Error, self.filename.clone(), err.span.start)
+                .with_message(&err.message)
+                .with_label(
+                    Label::new((self.filename.clone(), err.span.into_range()))
+                        .with_message(&err.reason)
+                        .with_color(Color::Red),
+                )
+                .with_labels(err.contexts.iter().map(|(label, span)| {
+                    Label::new((self.filename.clone(), span.into_range()))
+                        .with_message(label)
+                        .with_color(Color::Yellow)
+                }))
+                .finish()
+                .eprint(ariadne::sources([(self.filename.clone(), self.source.clone())]))
+                .unwrap()
+        }
+    }
+}
+
+/// Panics in deeply recursive functions would otherwise get a label for every call.
+const MAX_TRACE_LABELS: usize = 16;
+
+impl RuntimeError {
+    /// Prints a report to stderr, with a label on where the panic happened
+    /// and on each call that led there.
+    pub fn eprint(&self) {
+        let Some(location) = &self.location else {
+            eprintln!("{}", self);
+            return;
+        };
+        let id = |x: &Location| x.path.display().to_string();
+
+        let frames = &self.trace[..self.trace.len().min(MAX_TRACE_LABELS)];
+        let mut sources = Vec::new();
+        for location in iter::once(location).chain(frames.iter().map(|x| &x.location)) {
+            if !sources.iter().any(|(x, _)| x == &id(location)) {
+                sources.push((id(location), location.source.clone()));
+            }
+        }
+
+        let mut report = Report::build(ReportKind::Error, id(location), location.span.start)
+            .with_message(format!("Program panicked at '{}'", self.value))
+            .with_label(
+                Label::new((id(location), location.span.into_range()))
+                    .with_message("panicked here")
+                    .with_color(Color::Red),
+            )
+            .with_labels(frames.iter().enumerate().map(|(i, frame)| {
+                let message = match &frame.kind {
+                    FrameKind::Call(name) => format!("{}. `{}' called here", i + 1, name.id),
+                    FrameKind::Import(path) => format!("{}. {:?} imported here", i + 1, path),
+                };
+                Label::new((id(&frame.location), frame.location.span.into_range()))
+                    .with_message(message)
+                    .with_color(Color::Yellow)
+            }));
+        if self.trace.len() > frames.len() {
+            report = report.with_note(format!("{} more calls not shown", self.trace.len() - frames.len()));
+        }
+        report.finish().eprint(ariadne::sources(sources)).unwrap();
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for err in &self.errors {
+            let before = self.source.get(..err.span.start).unwrap_or_default();
+            let line = before.matches('\n').count() + 1;
+            let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+            writeln!(f, "{}:{}:{}: {}", self.filename, line, column, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
@@ -1,6 +1,7 @@
 use std::marker::ConstParamTy;
 
 use bitflags::bitflags;
+use chumsky::span::SimpleSpan;
 use enum_assoc::Assoc;
 
 use crate::parse::ident::Ident;
@@ -18,11 +19,31 @@ bitflags! {
 
 #[derive(Clone, Debug)]
 pub struct Program {
+    pub docs: Vec<String>,
     pub stmts: Vec<Stmt>,
 }
 
 #[derive(Clone, Debug)]
-pub enum Stmt {
+pub struct Stmt {
+    pub span: SimpleSpan,
+    pub kind: StmtKind,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: SimpleSpan) -> Self {
+        Self { span, kind }
+    }
+}
+
+impl From<Expr> for Stmt {
+    fn from(expr: Expr) -> Self {
+        let span = expr.span;
+        Self::new(StmtKind::Expr(expr), span)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum StmtKind {
     Decl(Decl),
     Expr(Expr),
     Assignment(Assignment),
@@ -34,10 +55,25 @@ pub enum Stmt {
 pub enum Decl {
     LetDecl(LetDecl),
     FnDecl(FnDecl),
+    Import(ImportDecl),
+    Use(UseDecl),
+}
+
+impl Decl {
+    pub fn span(&self) -> SimpleSpan {
+        match self {
+            Decl::LetDecl(x) => x.span,
+            Decl::FnDecl(x) => x.span,
+            Decl::Import(x) => x.span,
+            Decl::Use(x) => x.span,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct FnDecl {
+    pub span: SimpleSpan,
+    pub docs: Vec<String>,
     pub modifiers: Modifiers,
     pub name: Ident,
     pub args: Vec<FnArg>,
@@ -47,22 +83,74 @@ pub struct FnDecl {
 
 #[derive(Clone, Debug)]
 pub struct FnArg {
+    pub span: SimpleSpan,
     pub name: Ident,
     pub ty: Ident,
+    pub default: Option<Expr>,
     pub variadic: bool,
 }
 
+/// An argument passed to a function, either by position or by name.
+#[derive(Clone, Debug)]
+pub struct CallArg {
+    pub span: SimpleSpan,
+    pub name: Option<Ident>,
+    pub value: Expr,
+}
+
 #[derive(Clone, Debug)]
 pub struct LetDecl {
-    pub name: Ident,
+    pub span: SimpleSpan,
+    pub docs: Vec<String>,
+    pub modifiers: Modifiers,
+    pub pattern: Pattern,
     pub value: Expr,
 }
 
+/// Loads another file as a module, as in `import "chapters/one.nir" as one`.
+/// Without `as`, the module is named after the file.
+#[derive(Clone, Debug)]
+pub struct ImportDecl {
+    pub span: SimpleSpan,
+    pub path: String,
+    pub name: Ident,
+}
+
+/// Brings a `pub` item of a module into scope, as in `use one.intro as prologue`.
+#[derive(Clone, Debug)]
+pub struct UseDecl {
+    pub span: SimpleSpan,
+    pub module: Ident,
+    pub item: Ident,
+    pub name: Ident,
+}
+
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub span: SimpleSpan,
+    pub kind: ExprKind,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: SimpleSpan) -> Self {
+        Self { span, kind }
+    }
+}
+
 #[derive(Clone, Debug)]
-pub enum Expr {
+pub enum ExprKind {
     Lit(Lit),
     Object {
-        props: Vec<(ObjectPropName, Expr)>,
+        props: Vec<ObjectProp>,
+    },
+    Array {
+        items: Vec<Expr>,
+    },
+    Tuple {
+        items: Vec<Expr>,
+    },
+    Interpolated {
+        parts: Vec<InterpolatedPart>,
     },
     Var {
         name: Ident,
@@ -71,6 +159,10 @@ pub enum Expr {
         left: Box<Expr>,
         right: Ident,
     },
+    Index {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
     UnaryOp {
         ops: Vec<UnaryOp>,
         input: Box<Expr>,
@@ -82,7 +174,10 @@ pub enum Expr {
     },
     Call {
         target: Box<Expr>,
-        args: Vec<Expr>,
+        args: Vec<CallArg>,
+    },
+    Lambda {
+        decl: Box<FnDecl>,
     },
     Block {
         body: Vec<Stmt>,
@@ -99,6 +194,27 @@ pub enum Expr {
         condition: Box<Expr>,
         body: Vec<Stmt>,
     },
+    For {
+        binding: Ident,
+        iterable: Box<Expr>,
+        body: Vec<Stmt>,
+    },
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    /// Runs `body`, and if it panics, runs `handler` with the panic's
+    /// value bound to `binding`.
+    Try {
+        body: Vec<Stmt>,
+        binding: Ident,
+        handler: Vec<Stmt>,
+    },
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
     Error,
 }
 
@@ -107,9 +223,158 @@ pub enum Lit {
     Unit,
     Bool(bool),
     Char(char),
-    Int(u64),
-    Float(f64),
+    /// Negative literals like `-128i8` are folded into one by the parser.
+    Int(i128, Option<NumType>),
+    Float(f64, Option<NumType>),
+    Str(String),
+}
+
+impl Lit {
+    /// The type of an integer literal without a suffix.
+    pub fn default_int_type(x: i128) -> NumType {
+        if x < 0 {
+            NumType::I64
+        } else {
+            NumType::U64
+        }
+    }
+}
+
+/// The type of a numeric value, also used as a suffix on numeric literals (`10u8`, `1.5f32`).
+///
+/// When a binary operation is applied to two numbers of different types,
+/// both operands are first converted to a common type (see [`NumType::promote`]).
+#[derive(Assoc, Copy, Clone, Debug, PartialEq, Eq)]
+#[func(pub const fn name(&self) -> &'static str)]
+#[func(pub const fn bits(&self) -> u32)]
+#[func(pub const fn is_signed(&self) -> bool { true })]
+#[func(pub const fn is_float(&self) -> bool { false })]
+pub enum NumType {
+    #[assoc(name = "i8")]
+    #[assoc(bits = 8)]
+    I8,
+    #[assoc(name = "u8")]
+    #[assoc(bits = 8)]
+    #[assoc(is_signed = false)]
+    U8,
+    #[assoc(name = "i16")]
+    #[assoc(bits = 16)]
+    I16,
+    #[assoc(name = "u16")]
+    #[assoc(bits = 16)]
+    #[assoc(is_signed = false)]
+    U16,
+    #[assoc(name = "i32")]
+    #[assoc(bits = 32)]
+    I32,
+    #[assoc(name = "u32")]
+    #[assoc(bits = 32)]
+    #[assoc(is_signed = false)]
+    U32,
+    #[assoc(name = "i64")]
+    #[assoc(bits = 64)]
+    I64,
+    #[assoc(name = "u64")]
+    #[assoc(bits = 64)]
+    #[assoc(is_signed = false)]
+    U64,
+    #[assoc(name = "f32")]
+    #[assoc(bits = 32)]
+    #[assoc(is_float = true)]
+    F32,
+    #[assoc(name = "f64")]
+    #[assoc(bits = 64)]
+    #[assoc(is_float = true)]
+    F64,
+}
+
+impl NumType {
+    pub const ALL: [Self; 10] = [
+        Self::I8,
+        Self::U8,
+        Self::I16,
+        Self::U16,
+        Self::I32,
+        Self::U32,
+        Self::I64,
+        Self::U64,
+        Self::F32,
+        Self::F64,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+
+    /// Gets the integer type with the given size and signedness.
+    pub fn int(bits: u32, signed: bool) -> Self {
+        match (bits, signed) {
+            (8, true) => Self::I8,
+            (8, false) => Self::U8,
+            (16, true) => Self::I16,
+            (16, false) => Self::U16,
+            (32, true) => Self::I32,
+            (32, false) => Self::U32,
+            (64, true) => Self::I64,
+            (64, false) => Self::U64,
+            _ => panic!("no {}-bit integer type", bits),
+        }
+    }
+
+    /// Checks if `x` is in range for this integer type.
+    pub fn can_hold(self, x: i128) -> bool {
+        if self.is_float() {
+            false
+        } else if self.is_signed() {
+            (-(1 << (self.bits() - 1))..1 << (self.bits() - 1)).contains(&x)
+        } else {
+            (0..1 << self.bits()).contains(&x)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Ident),
+    Lit(Expr),
+    Range {
+        start: Expr,
+        end: Expr,
+        inclusive: bool,
+    },
+    Object {
+        props: Vec<(Ident, Pattern)>,
+    },
+    /// `rest` matches the remaining elements after `items`, as in `[first, ...rest]`.
+    Array {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+    Tuple {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum InterpolatedPart {
     Str(String),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjectProp {
+    pub span: SimpleSpan,
+    pub name: ObjectPropName,
+    pub value: Expr,
 }
 
 #[derive(Clone, Debug)]
@@ -191,11 +456,18 @@ pub enum BinaryOp {
 
 #[derive(Clone, Debug)]
 pub struct Assignment {
-    pub path: Vec<Ident>,
+    pub target: Ident,
+    pub path: Vec<PathSegment>,
     pub value: Expr,
     pub op: Option<BinaryOp>,
 }
 
+#[derive(Clone, Debug)]
+pub enum PathSegment {
+    Property(Ident),
+    Index(Expr),
+}
+
 #[derive(Clone, Debug)]
 pub enum ControlFlow {
     Continue,
@@ -1,13 +1,18 @@
+mod comments;
 mod numbers;
 mod text;
 pub mod token;
 
-use chumsky::prelude::{any, choice, end, one_of, skip_then_retry_until, IterParser, Parser};
+#[cfg(test)]
+mod tests;
+
+use chumsky::prelude::{any, end, one_of, skip_then_retry_until, IterParser, Parser};
+use smallvec::{smallvec, SmallVec};
 
 use crate::parse::ast::{BinaryOp, UnaryOp};
 use crate::parse::ident::Ident;
 use crate::parse::lexer::token::{Ctrl, Keyword, Token};
-use crate::parse::utils::just_str;
+use crate::parse::utils::{just_str, just_word};
 use crate::parse::Spanned;
 
 pub(super) macro Lexer($s:lifetime, $output:ty) {
@@ -15,9 +20,9 @@ pub(super) macro Lexer($s:lifetime, $output:ty) {
 }
 
 pub fn lexer<'s>() -> Lexer!['s, Vec<Spanned<Token>>] {
-    let bool = just_str("true")
+    let bool = just_word("true")
         .to(Token::Bool(true))
-        .or(just_str("false").to(Token::Bool(false)))
+        .or(just_word("false").to(Token::Bool(false)))
         .labelled("bool");
 
     let op = just_str("**")
@@ -28,8 +33,8 @@ pub fn lexer<'s>() -> Lexer!['s, Vec<Spanned<Token>>] {
         .or(just_str("!=").to(BinaryOp::Neq))
         .or(just_str("<=").to(BinaryOp::Lte))
         .or(just_str(">=").to(BinaryOp::Gte))
-        .or(choice([just_str("&&"), just_str("and")]).to(BinaryOp::And))
-        .or(choice([just_str("||"), just_str("or")]).to(BinaryOp::Or))
+        .or(just_str("&&").or(just_word("and")).to(BinaryOp::And))
+        .or(just_str("||").or(just_word("or")).to(BinaryOp::Or))
         .or(one_of("+-*/%&|^⟲⟳<>").map(BinaryOp::from_char).unwrapped())
         .map(Token::Op)
         .labelled("op");
@@ -40,7 +45,13 @@ pub fn lexer<'s>() -> Lexer!['s, Vec<Spanned<Token>>] {
         .map(Token::UnaryOp)
         .labelled("unary op");
 
-    let ctrl = one_of("()[]{}:;,.=").map(Ctrl::from_char).unwrapped().map(Token::Ctrl);
+    let ctrl = just_str("...")
+        .to(Ctrl::Ellipsis)
+        .or(just_str("..=").to(Ctrl::DotDotEq))
+        .or(just_str("..").to(Ctrl::DotDot))
+        .or(just_str("=>").to(Ctrl::FatArrow))
+        .or(one_of("()[]{}:;,.=").map(Ctrl::from_char).unwrapped())
+        .map(Token::Ctrl);
 
     let keyword = Keyword::lexer().map(Token::Keyword).labelled("keyword");
 
@@ -49,16 +60,28 @@ pub fn lexer<'s>() -> Lexer!['s, Vec<Spanned<Token>>] {
         .map(Token::Ident)
         .labelled("identifier");
 
-    op.or(unary_op)
+    let comment = comments::comment().padded();
+
+    let token = comments::doc_comment()
+        .or(op)
+        .or(unary_op)
         .or(ctrl)
         .or(bool)
         .or(keyword)
-        .or(ident)
         .or(numbers::lexer())
-        .or(text::lexer())
-        .map_with_span(|a, b| (a, b))
+        .or(ident)
+        .map_with_span(|a, b| smallvec![(a, b)]);
+
+    // string literals go first so that raw strings aren't lexed as identifiers,
+    // and lex to several tokens if they contain interpolations
+    text::lexer()
+        .or(token)
+        .padded_by(comment.clone().repeated())
         .padded()
         .recover_with(skip_then_retry_until(any().ignored(), end()))
         .repeated()
-        .collect()
+        .collect::<Vec<SmallVec<[Spanned<Token>; 1]>>>()
+        .map(|x| x.into_iter().flatten().collect())
+        .padded_by(comment.repeated())
+        .padded()
 }
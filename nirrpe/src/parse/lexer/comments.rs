@@ -0,0 +1,51 @@
+use chumsky::error::Rich;
+use chumsky::prelude::{any, just, recursive, Parser};
+
+use crate::parse::lexer::token::Token;
+use crate::parse::lexer::Lexer;
+use crate::parse::utils::just_str;
+
+/// Lexes `///` and `//!` doc comments, which are kept as tokens
+/// so that the parser can attach them to declarations.
+pub fn doc_comment<'s>() -> Lexer!['s, Token] {
+    let outer = just_str("///")
+        .then(just('/').not())
+        .ignore_then(rest_of_line())
+        .map(Token::DocComment);
+    let inner = just_str("//!").ignore_then(rest_of_line()).map(Token::InnerDocComment);
+    outer.or(inner).labelled("doc comment")
+}
+
+/// Lexes `//` line comments and (nestable) `/* */` block comments.
+/// Doc comments are not matched by this lexer.
+pub fn comment<'s>() -> Lexer!['s, ()] {
+    let line_comment = just_str("//")
+        .and_is(doc_comment().not())
+        .then(rest_of_line())
+        .ignored();
+
+    let block_comment = recursive(|block_comment| {
+        just_str("/*")
+            .then(
+                block_comment
+                    .or(any().and_is(just_str("*/").not()).ignored())
+                    .repeated(),
+            )
+            .then(just_str("*/").or_not())
+            .validate(|(_, closed), span, emitter| {
+                if closed.is_none() {
+                    emitter.emit(Rich::custom(span, "unterminated block comment"));
+                }
+            })
+    });
+
+    line_comment.or(block_comment).labelled("comment")
+}
+
+fn rest_of_line<'s>() -> Lexer!['s, String] {
+    any()
+        .and_is(just('\n').not())
+        .repeated()
+        .slice()
+        .map(|x: &str| x.strip_suffix('\r').unwrap_or(x).to_string())
+}
@@ -1,25 +1,27 @@
 use std::num::ParseIntError;
 
+use chumsky::error::Rich;
 use chumsky::prelude::{choice, just, one_of, via_parser, Parser};
 use chumsky::text;
 
+use crate::parse::ast::NumType;
 use crate::parse::lexer::token::Token;
 use crate::parse::lexer::Lexer;
-use crate::parse::utils::{just_str, ParserTryUnwrapped};
+use crate::parse::utils::{just_word, ParserTryUnwrapped};
 
 pub fn lexer<'s>() -> Lexer!['s, Token] {
     let hex_header = just('0').ignore_then(one_of("xX"));
     let hex_int = hex_header
         .ignore_then(num_with_separators::<16>())
         .try_unwrapped()
-        .map(Token::Int)
+        .map(|x| Token::Int(x, None))
         .recover_with(via_parser(hex_header.ignore_then(num_with_separators_ignored::<16>())));
 
     let bin_header = just('0').ignore_then(one_of("bB"));
     let bin_int = bin_header
         .ignore_then(num_with_separators::<2>())
         .try_unwrapped()
-        .map(Token::Int)
+        .map(|x| Token::Int(x, None))
         .recover_with(via_parser(bin_header.ignore_then(num_with_separators_ignored::<2>())));
 
     let float_part = text::digits(10)
@@ -30,12 +32,12 @@ pub fn lexer<'s>() -> Lexer!['s, Token] {
         .ignore_then(one_of("+-").or_not().ignored())
         .ignore_then(float_part);
     let float = choice([
-        just_str("inf"),
-        just_str("Inf"),
-        just_str("infinity"),
-        just_str("Infinity"),
-        just_str("NaN"),
-        just_str("nan"),
+        just_word("inf"),
+        just_word("Inf"),
+        just_word("infinity"),
+        just_word("Infinity"),
+        just_word("NaN"),
+        just_word("nan"),
     ])
     .slice()
     .or(float_part
@@ -44,14 +46,53 @@ pub fn lexer<'s>() -> Lexer!['s, Token] {
         .then(float_part)
         .then(float_exp.or_not())
         .slice())
-    .or(float_part.then(just('.')).then(float_exp.or_not()).slice())
+    // don't eat the start of a range like `0..10`
+    .or(float_part
+        .then(just('.'))
+        .then(just('.').not())
+        .then(float_exp.or_not())
+        .slice())
     .or(float_part.ignore_then(float_exp).slice())
     .map(|x: &str| x.replace('_', "").parse::<f64>())
     .try_unwrapped()
-    .map(Token::Float);
+    .map(|x| Token::Float(x, None));
 
-    let int = num_with_separators::<10>().try_unwrapped().map(Token::Int);
-    hex_int.or(bin_int).or(float).or(int).labelled("number")
+    let int = num_with_separators::<10>().try_unwrapped().map(|x| Token::Int(x, None));
+
+    hex_int
+        .or(bin_int)
+        .or(float)
+        .or(int)
+        .then(text::unicode::ident().or_not())
+        .validate(|(token, suffix), span, emitter| match with_suffix(token, suffix) {
+            Ok(token) => token,
+            Err(err) => {
+                emitter.emit(Rich::custom(span, err));
+                Token::Err
+            }
+        })
+        .labelled("number")
+}
+
+/// Applies a type suffix like `u8` or `f32` to a number token.
+fn with_suffix(token: Token, suffix: Option<&str>) -> Result<Token, String> {
+    let Some(suffix) = suffix else {
+        return Ok(token);
+    };
+    let Some(ty) = NumType::from_name(suffix) else {
+        return Err(format!("invalid suffix `{}' for number literal", suffix));
+    };
+    match token {
+        Token::Int(x, _) if ty.is_float() => Ok(Token::Float(x as f64, Some(ty))),
+        // checked by the type checker once any `-` is folded in
+        Token::Int(x, _) => Ok(Token::Int(x, Some(ty))),
+        Token::Float(_, _) if !ty.is_float() => Err(format!("invalid suffix `{}' for float literal", suffix)),
+        Token::Float(x, _) if ty == NumType::F32 && x.is_finite() && (x as f32).is_infinite() => {
+            Err(format!("literal `{}' out of range for f32", x))
+        }
+        Token::Float(x, _) => Ok(Token::Float(x, Some(ty))),
+        token => Ok(token),
+    }
 }
 
 fn num_with_separators<'s, const RADIX: u32>() -> Lexer!['s, Result<u64, ParseIntError>] {
@@ -0,0 +1,106 @@
+use chumsky::Parser;
+
+use crate::parse::ast::BinaryOp;
+use crate::parse::ident::Ident;
+use crate::parse::lexer::lexer;
+use crate::parse::lexer::token::{Ctrl, Keyword, Token};
+
+fn lex(src: &str) -> Vec<Token> {
+    lexer()
+        .parse(src)
+        .into_result()
+        .unwrap_or_else(|errs| panic!("{:?} should lex, but got {:?}", src, errs))
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect()
+}
+
+/// Words that only lex as something other than an identifier when they're
+/// the whole identifier, along with what they lex to.
+fn reserved_words() -> Vec<(&'static str, Token)> {
+    let mut words = Keyword::ALL
+        .iter()
+        .map(|x| (x.keyword(), Token::Keyword(x.clone())))
+        .collect::<Vec<_>>();
+    words.extend([
+        ("true", Token::Bool(true)),
+        ("false", Token::Bool(false)),
+        ("inf", Token::Float(f64::INFINITY, None)),
+        ("Inf", Token::Float(f64::INFINITY, None)),
+        ("infinity", Token::Float(f64::INFINITY, None)),
+        ("Infinity", Token::Float(f64::INFINITY, None)),
+        ("and", Token::Op(BinaryOp::And)),
+        ("or", Token::Op(BinaryOp::Or)),
+    ]);
+    words
+}
+
+fn assert_ident(src: &str) {
+    assert_eq!(
+        lex(src),
+        vec![Token::Ident(Ident::new(src))],
+        "{:?} should lex as a single identifier",
+        src
+    );
+}
+
+#[test]
+fn reserved_words_lex_on_their_own() {
+    for (word, token) in reserved_words() {
+        assert_eq!(lex(word), vec![token], "{:?} should lex as a reserved word", word);
+    }
+    for word in ["nan", "NaN"] {
+        assert!(
+            matches!(lex(word)[..], [Token::Float(x, None)] if x.is_nan()),
+            "{:?} should lex as NaN",
+            word
+        );
+    }
+}
+
+#[test]
+fn reserved_words_with_a_prefix_or_suffix_are_identifiers() {
+    let words = reserved_words().into_iter().map(|(word, _)| word).chain(["nan", "NaN"]);
+    for word in words {
+        assert_ident(&format!("my{}", word));
+        assert_ident(&format!("{}s", word));
+        assert_ident(&format!("{}_", word));
+    }
+}
+
+#[test]
+fn identifiers_starting_with_reserved_words_arent_split() {
+    for name in [
+        "index",
+        "format",
+        "order",
+        "inform",
+        "trueish",
+        "falsehood",
+        "android",
+        "ifs",
+        "lettuce",
+    ] {
+        assert_ident(name);
+    }
+}
+
+#[test]
+fn reserved_words_are_split_by_punctuation() {
+    assert_eq!(
+        lex("for(in)"),
+        vec![
+            Token::Keyword(Keyword::For),
+            Token::Ctrl(Ctrl::LeftParen),
+            Token::Keyword(Keyword::In),
+            Token::Ctrl(Ctrl::RightParen),
+        ]
+    );
+}
+
+#[test]
+fn closing_braces_in_strings_are_literal() {
+    assert_eq!(lex(r#""a}b""#), vec![Token::Str("a}b".to_string())]);
+    assert_eq!(lex(r#""}}""#), vec![Token::Str("}}".to_string())]);
+    assert_eq!(lex(r#""{{}""#), vec![Token::Str("{}".to_string())]);
+}
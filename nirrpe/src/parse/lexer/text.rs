@@ -1,13 +1,21 @@
-use chumsky::prelude::{any, just, none_of, via_parser};
+use std::iter;
+
+use chumsky::error::Rich;
+use chumsky::prelude::{any, just, none_of, one_of, recursive, via_parser, SimpleSpan};
 use chumsky::{IterParser, Parser};
+use smallvec::{smallvec, SmallVec};
 
 use crate::parse::lexer::token::Token;
 use crate::parse::lexer::Lexer;
 use crate::parse::utils::{just_str, n_digits, recover_delimited_by, ParserTryUnwrapped};
+use crate::parse::Spanned;
 
 const REPLACEMENT: char = '\u{fffd}';
 
-pub fn lexer<'s>() -> Lexer!['s, Token] {
+/// The most `#`s that a raw string can be delimited by.
+const MAX_RAW_STRING_HASHES: usize = 8;
+
+pub fn lexer<'s>() -> Lexer!['s, SmallVec<[Spanned<Token>; 1]>] {
     let char_non_escape = none_of(r#""\"#);
 
     let char_x_escape = unicode_fixed_width_escape('x', 2);
@@ -103,24 +111,177 @@ pub fn lexer<'s>() -> Lexer!['s, Token] {
         .or(char_control_escape)
         .or(char_meta_escape);
 
-    let string_lit = char
-        .clone()
+    // only `{` starts an interpolation, so `}` is always literal
+    let string_char = just_str("{{").to('{').or(char.clone().and_is(just('{').not()));
+
+    let string_text = string_char
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+        .map_with_span(StringPart::Text);
+
+    let interpolation = just('{')
+        .map_with_span(|_, span: SimpleSpan| span)
+        .then(interpolation_source().map_with_span(|x, span: SimpleSpan| (x, span)))
+        .then(just('}').map_with_span(|_, span: SimpleSpan| span))
+        .validate(|((start, (src, src_span)), end), _, emitter| {
+            // the interpolated expression is lexed on its own, then its spans are shifted back into place
+            let (tokens, errs) = super::lexer().parse(src).into_output_errors();
+            for err in errs {
+                emitter.emit(Rich::custom(shift_span(*err.span(), src_span.start), err.to_string()));
+            }
+            let tokens = tokens
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(token, span)| (token, shift_span(span, src_span.start)));
+            StringPart::Interpolation(
+                iter::once((Token::InterpolationStart, start))
+                    .chain(tokens)
+                    .chain(iter::once((Token::InterpolationEnd, end)))
+                    .collect(),
+            )
+        })
+        .labelled("string interpolation");
+
+    let string_lit = string_text
+        .or(interpolation)
         .repeated()
-        .collect()
-        .padded_by(just('"'))
-        .map(Token::Str)
-        .recover_with(via_parser(recover_delimited_by('"', '"').to(Token::Err)))
+        .collect::<Vec<_>>()
+        .delimited_by(just('"'), just('"'))
+        .map_with_span(string_tokens)
+        .recover_with(via_parser(
+            recover_delimited_by('"', '"').map_with_span(|_, span| smallvec![(Token::Err, span)]),
+        ))
         .labelled("string literal");
+    let raw_string_lit = (1..=MAX_RAW_STRING_HASHES)
+        .fold(raw_string(0).boxed(), |acc, hashes| acc.or(raw_string(hashes)).boxed())
+        .labelled("raw string literal");
+
+    let text_block_delimiter = just_str("\"\"\"");
+    let text_block_lit = text_block_delimiter
+        .clone()
+        .ignore_then(one_of(" \t").repeated())
+        .ignore_then(just_str("\r\n").ignored().or(just('\n').ignored()))
+        .ignore_then(any().and_is(text_block_delimiter.clone().not()).repeated().slice())
+        .then_ignore(text_block_delimiter)
+        .map(strip_indent)
+        .labelled("text block literal");
+
+    let string_lit = text_block_lit
+        .or(raw_string_lit)
+        .map_with_span(|x, span| smallvec![(Token::Str(x), span)])
+        .or(string_lit);
     #[rustfmt::skip]
     let char_lit = char
         .padded_by(just('\''))
         .map(Token::Char)
         .recover_with(via_parser(recover_delimited_by('\'', '\'').to(Token::Char(REPLACEMENT))))
+        .map_with_span(|x, span| smallvec![(x, span)])
         .labelled("char literal");
 
     string_lit.or(char_lit)
 }
 
+enum StringPart {
+    Text(String, SimpleSpan),
+    Interpolation(Vec<Spanned<Token>>),
+}
+
+/// Turns the parts of a string literal into a single [`Token::Str`], or into a
+/// [`Token::StrStart`] ... [`Token::StrEnd`] sequence if it has any interpolations.
+fn string_tokens(parts: Vec<StringPart>, span: SimpleSpan) -> SmallVec<[Spanned<Token>; 1]> {
+    if parts.iter().all(|x| matches!(x, StringPart::Text(..))) {
+        let text = parts
+            .into_iter()
+            .map(|x| match x {
+                StringPart::Text(x, _) => x,
+                StringPart::Interpolation(_) => unreachable!(),
+            })
+            .collect();
+        smallvec![(Token::Str(text), span)]
+    } else {
+        let mut tokens: SmallVec<[Spanned<Token>; 1]> =
+            smallvec![(Token::StrStart, (span.start..span.start + 1).into())];
+        for part in parts {
+            match part {
+                StringPart::Text(x, span) => tokens.push((Token::Str(x), span)),
+                StringPart::Interpolation(x) => tokens.extend(x),
+            }
+        }
+        tokens.push((Token::StrEnd, (span.end - 1..span.end).into()));
+        tokens
+    }
+}
+
+/// Lexes the source of an interpolated expression up to its closing brace,
+/// skipping over any nested braces, strings and chars.
+fn interpolation_source<'s>() -> Lexer!['s, &'s str] {
+    let string = just('"')
+        .then(
+            none_of("\"\\")
+                .ignored()
+                .or(just('\\').then(any()).ignored())
+                .repeated(),
+        )
+        .then(just('"'))
+        .ignored();
+    let char = just('\'')
+        .then(none_of("'\\").ignored().or(just('\\').then(any()).ignored()).repeated())
+        .then(just('\''))
+        .ignored();
+    recursive(|source| {
+        none_of("{}\"'")
+            .ignored()
+            .or(string)
+            .or(char)
+            .or(source.delimited_by(just('{'), just('}')))
+            .repeated()
+    })
+    .slice()
+}
+
+/// Lexes a raw string delimited by exactly `hashes` `#`s, like `r#"..."#`.
+fn raw_string<'s>(hashes: usize) -> Lexer!['s, String] {
+    let end = just('"').then(just('#').repeated().exactly(hashes));
+    just('r')
+        .then(just('#').repeated().exactly(hashes))
+        .then(just('"'))
+        .ignore_then(any().and_is(end.clone().not()).repeated().slice())
+        .then_ignore(end)
+        .map(ToString::to_string)
+}
+
+/// Removes the indentation shared by every line of a text block. The line
+/// holding the closing delimiter counts towards the indentation, but is
+/// otherwise dropped if it's blank.
+fn strip_indent(text: &str) -> String {
+    let mut lines = text
+        .split('\n')
+        .map(|x| x.strip_suffix('\r').unwrap_or(x))
+        .collect::<Vec<_>>();
+    let indent_of = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let last = lines.pop().unwrap_or_default();
+    let indent = lines
+        .iter()
+        .filter(|x| !x.trim().is_empty())
+        .chain(iter::once(&last))
+        .map(|x| indent_of(x))
+        .min()
+        .unwrap_or_default();
+    if !last.trim().is_empty() {
+        lines.push(last);
+    }
+    lines
+        .into_iter()
+        .map(|x| &x[indent.min(indent_of(x))..])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn shift_span(span: SimpleSpan, offset: usize) -> SimpleSpan {
+    (span.start + offset..span.end + offset).into()
+}
+
 fn unicode_fixed_width_escape<'s>(escape: char, width: usize) -> Lexer!['s, char] {
     let prefix = just('\\').ignore_then(just(escape)).ignored();
     prefix
@@ -2,7 +2,7 @@ use std::marker::ConstParamTy;
 
 use enum_assoc::Assoc;
 
-use crate::parse::ast::{BinaryOp, UnaryOp};
+use crate::parse::ast::{BinaryOp, NumType, UnaryOp};
 use crate::parse::ident::Ident;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -10,9 +10,18 @@ pub enum Token {
     Err,
     Bool(bool),
     Char(char),
-    Int(u64),
-    Float(f64),
+    Int(u64, Option<NumType>),
+    Float(f64, Option<NumType>),
     Str(String),
+    /// Starts a string literal containing interpolations. The literal's text is
+    /// split into [`Token::Str`]s, and each interpolated expression is wrapped in
+    /// [`Token::InterpolationStart`] and [`Token::InterpolationEnd`].
+    StrStart,
+    StrEnd,
+    InterpolationStart,
+    InterpolationEnd,
+    DocComment(String),
+    InnerDocComment(String),
     UnaryOp(UnaryOp),
     Op(BinaryOp),
     Ctrl(Ctrl),
@@ -45,6 +54,10 @@ pub enum Ctrl {
     Period,
     #[assoc(from_char = '=')]
     Eq,
+    DotDot,
+    DotDotEq,
+    Ellipsis,
+    FatArrow,
 }
 
 /// https://github.com/rust-lang/rust/issues/113638
@@ -63,12 +76,15 @@ macro_rules! gen_keyword_lexer {
         }
 
         impl $name {
+            /// Every variant, in declaration order.
+            pub const ALL: &'static [Self] = &[Self::$first_variant, $(Self::$variant),*];
+
             pub fn lexer<'s>() -> $crate::parse::lexer::Lexer!['s, Self] {
                 use ::chumsky::Parser;
-                $crate::parse::utils::just_str(Self::$first_variant.keyword())
+                $crate::parse::utils::just_word(Self::$first_variant.keyword())
                     .to(Self::$first_variant)
                     $(
-                        .or($crate::parse::utils::just_str(Self::$variant.keyword()).to(Self::$variant))
+                        .or($crate::parse::utils::just_word(Self::$variant.keyword()).to(Self::$variant))
                     )*
             }
         }
@@ -79,8 +95,12 @@ gen_keyword_lexer! {
     #[derive(Assoc, Clone, Debug, PartialEq)]
     #[func(pub const fn keyword(&self) -> &'static str)]
     pub enum Keyword {
+        #[assoc(keyword = "as")]
+        As,
         #[assoc(keyword = "break")]
         Break,
+        #[assoc(keyword = "catch")]
+        Catch,
         #[assoc(keyword = "continue")]
         Continue,
         #[assoc(keyword = "else")]
@@ -97,10 +117,14 @@ gen_keyword_lexer! {
         In,
         #[assoc(keyword = "impure")]
         Impure,
+        #[assoc(keyword = "import")]
+        Import,
         #[assoc(keyword = "let")]
         Let,
         #[assoc(keyword = "loop")]
         Loop,
+        #[assoc(keyword = "match")]
+        Match,
         #[assoc(keyword = "priv")]
         Priv,
         #[assoc(keyword = "pub")]
@@ -109,6 +133,10 @@ gen_keyword_lexer! {
         Pure,
         #[assoc(keyword = "return")]
         Return,
+        #[assoc(keyword = "try")]
+        Try,
+        #[assoc(keyword = "use")]
+        Use,
         #[assoc(keyword = "while")]
         While,
     }
@@ -3,7 +3,12 @@ pub mod ident;
 pub mod lexer;
 pub mod utils;
 
+#[cfg(test)]
+mod tests;
+
 use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
 
 use bitflags::Flags;
 use chumsky::error::{Error as ChumskyError, Rich};
@@ -18,8 +23,9 @@ use ordinal::Ordinal;
 use smallvec::SmallVec;
 
 use crate::parse::ast::{
-    Assignment, BinaryOp, ControlFlow, Decl, Expr, FnArg, FnDecl, LetDecl, Lit, Modifiers, ObjectPropName, Program,
-    Stmt, UnaryOp,
+    Assignment, BinaryOp, CallArg, ControlFlow, Decl, Expr, ExprKind, FnArg, FnDecl, ImportDecl, InterpolatedPart,
+    LetDecl, Lit, MatchArm, Modifiers, ObjectProp, ObjectPropName, PathSegment, Pattern, Program, Stmt, StmtKind,
+    UnaryOp, UseDecl,
 };
 use crate::parse::ident::Ident;
 use crate::parse::lexer::token::{Ctrl, Keyword, Token};
@@ -51,19 +57,44 @@ macro Parser($s:lifetime, $output:ty $(, $($extra:lifetime),*$(,)?)?) {
 pub type Spanned<T> = (T, SimpleSpan);
 
 pub fn parser<'s>() -> Parser!['s, Program] {
-    recursive(|stmts| {
+    let inner_docs = select! {
+        Token::InnerDocComment(x) => x,
+    }
+    .repeated()
+    .collect::<Vec<_>>();
+
+    let stmts = recursive(|stmts| {
         let expr = expr(stmts.clone());
         let ident = ident();
 
-        let r#let = just(Token::Keyword(Keyword::Let))
-            .ignore_then(ident.clone())
+        let r#let = doc_comments()
+            .then(visibility())
+            .then_ignore(just(Token::Keyword(Keyword::Let)))
+            .then(pattern())
             .then_ignore(just(Token::Ctrl(Ctrl::Eq)))
             .then(expr.clone())
-            .map(|(name, value)| Stmt::Decl(Decl::LetDecl(LetDecl { name, value })))
+            .map_with_span(|(((docs, modifiers), pattern), value), span| {
+                StmtKind::Decl(Decl::LetDecl(LetDecl {
+                    span,
+                    docs,
+                    modifiers,
+                    pattern,
+                    value,
+                }))
+            })
             .labelled("let declaration".into());
+        let path_segment = just(Token::Ctrl(Ctrl::Period))
+            .ignore_then(ident.clone())
+            .map(PathSegment::Property)
+            .or(expr
+                .clone()
+                .delimited_by(
+                    just(Token::Ctrl(Ctrl::LeftBracket)),
+                    just(Token::Ctrl(Ctrl::RightBracket)),
+                )
+                .map(PathSegment::Index));
         let assignment = ident
-            .separated_by(just(Token::Ctrl(Ctrl::Period)))
-            .collect::<Vec<_>>()
+            .then(path_segment.repeated().collect::<Vec<_>>())
             .then(
                 any()
                     .try_map(|x, span| match x {
@@ -87,37 +118,82 @@ pub fn parser<'s>() -> Parser!['s, Program] {
                     }),
             )
             .then(expr.clone())
-            .map(|((path, op), value)| Stmt::Assignment(Assignment { path, value, op }))
+            .map(|(((target, path), op), value)| {
+                StmtKind::Assignment(Assignment {
+                    target,
+                    path,
+                    value,
+                    op,
+                })
+            })
             .labelled("variable assignment".into());
 
         let r#continue = just(Token::Keyword(Keyword::Continue))
-            .to(Stmt::ControlFlow(ControlFlow::Continue))
+            .to(StmtKind::ControlFlow(ControlFlow::Continue))
             .labelled("continue statement".into());
         let r#break = just(Token::Keyword(Keyword::Break))
             .ignore_then(expr.clone().or_not())
-            .map(|x| Stmt::ControlFlow(ControlFlow::Break(x)))
+            .map(|x| StmtKind::ControlFlow(ControlFlow::Break(x)))
             .labelled("break statement".into());
         let r#return = just(Token::Keyword(Keyword::Return))
             .ignore_then(expr.clone().or_not())
-            .map(|x| Stmt::ControlFlow(ControlFlow::Return(x)))
+            .map(|x| StmtKind::ControlFlow(ControlFlow::Return(x)))
             .labelled("return statement".into());
 
-        decl(stmts)
-            .map(Stmt::Decl)
-            .or(r#let)
-            .or(assignment)
-            .or(expr.map(Stmt::Expr))
-            .or(r#continue)
-            .or(r#break)
-            .or(r#return)
+        let decl = decl(stmts).map(StmtKind::Decl);
+        let documented = decl.clone().or(r#let).map_with_span(Stmt::new);
+        // doc comments are only kept on functions and variables, and are ignored anywhere else
+        let undocumented = doc_comments().ignore_then(
+            decl.or(assignment)
+                .or(expr.map(StmtKind::Expr))
+                .or(r#continue)
+                .or(r#break)
+                .or(r#return)
+                .map_with_span(Stmt::new),
+        );
+
+        documented
+            .or(undocumented)
             .labelled("statement".into())
             .separated_by(just(Token::Ctrl(Ctrl::Semicolon)).repeated().ignored())
             .allow_leading()
             .allow_trailing()
-            .collect()
-    })
-    .map(|x| Program { stmts: x })
-    .then_ignore(end())
+            .collect::<Vec<_>>()
+            .then_ignore(doc_comments())
+    });
+
+    inner_docs
+        .then(stmts)
+        .map(|(docs, stmts)| Program { docs, stmts })
+        .then_ignore(end())
+}
+
+/// How deeply brackets, braces and interpolations can be nested, so that
+/// parsing and checking a program can't overflow the stack.
+pub const MAX_NESTING_DEPTH: usize = 256;
+
+/// Rejects programs nested more than [`MAX_NESTING_DEPTH`] deep, before
+/// they're parsed.
+pub fn check_nesting(tokens: &[Spanned<Token>]) -> Result<(), Rich<Token, SimpleSpan, String>> {
+    let mut depth = 0usize;
+    for (token, span) in tokens {
+        match token {
+            Token::Ctrl(Ctrl::LeftParen | Ctrl::LeftBracket | Ctrl::LeftBrace) | Token::InterpolationStart => {
+                depth += 1;
+                if depth > MAX_NESTING_DEPTH {
+                    return Err(Rich::custom(
+                        *span,
+                        format!("nested more than {} deep", MAX_NESTING_DEPTH),
+                    ));
+                }
+            }
+            Token::Ctrl(Ctrl::RightParen | Ctrl::RightBracket | Ctrl::RightBrace) | Token::InterpolationEnd => {
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
 }
 
 pub fn decl<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, ParserExtra<'s>>>) -> Parser!['s, Decl] {
@@ -179,23 +255,39 @@ pub fn decl<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
         })
         .labelled("function modifiers".into());
 
-        let args = ident
-            .clone()
+        let args = just(Token::Ctrl(Ctrl::Ellipsis))
+            .or_not()
+            .then(ident.clone())
             .then_ignore(just(Token::Ctrl(Ctrl::Colon)))
             .then(ident.clone())
-            .map(|(name, ty)| FnArg {
-                name,
-                ty,
-                variadic: false,
+            .then(just(Token::Ctrl(Ctrl::Eq)).ignore_then(expr(stmts.clone())).or_not())
+            .validate(|(((variadic, name), ty), default), span, emitter| {
+                if variadic.is_some() && default.is_some() {
+                    emitter.emit(Rich::custom(span, "variadic arguments can't have a default value"));
+                }
+                FnArg {
+                    span,
+                    name,
+                    ty,
+                    default,
+                    variadic: variadic.is_some(),
+                }
             })
             .labelled("function argument".into())
             .separated_by(just(Token::Ctrl(Ctrl::Comma)))
             .allow_trailing()
-            .collect()
+            .collect::<Vec<_>>()
+            .validate(|args, span, emitter| {
+                if has_misplaced_variadic(&args) {
+                    emitter.emit(Rich::custom(span, "only the last argument can be variadic"));
+                }
+                args
+            })
             .delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen)))
             .labelled("function arguments".into());
 
-        modifiers
+        doc_comments()
+            .then(modifiers)
             .then_ignore(just(Token::Keyword(Keyword::Fn)))
             .then(ident.clone().labelled("function name".into()))
             .then(args)
@@ -210,16 +302,18 @@ pub fn decl<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
                     .clone()
                     .delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace)))
                     .recover_with(via_parser(
-                        nested_recovery::<{ Ctrl::LeftBrace }, { Ctrl::RightBrace }>().map(|x| vec![Stmt::Expr(x)]),
+                        nested_recovery::<{ Ctrl::LeftBrace }, { Ctrl::RightBrace }>().map(|x| vec![Stmt::from(x)]),
                     ))
                     .or(just(Token::Ctrl(Ctrl::Eq))
                         .ignore_then(expr(stmts))
-                        .map(|x| vec![Stmt::Expr(x)]))
+                        .map(|x| vec![Stmt::from(x)]))
                     .labelled("function body".into())
                     .or_not(),
             )
-            .map(|((((modifiers, name), args), return_ty), body)| {
+            .map_with_span(|(((((docs, modifiers), name), args), return_ty), body), span| {
                 Decl::FnDecl(FnDecl {
+                    span,
+                    docs,
                     modifiers,
                     name,
                     args,
@@ -229,19 +323,57 @@ pub fn decl<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
             })
             .labelled("function".into())
     };
-    r#fn
+
+    let import = just(Token::Keyword(Keyword::Import))
+        .ignore_then(select! { Token::Str(x) => x }.labelled("module path".into()))
+        .then(just(Token::Keyword(Keyword::As)).ignore_then(ident()).or_not())
+        .validate(|(path, name), span, emitter| {
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    let stem = Path::new(&path).file_stem().and_then(OsStr::to_str).unwrap_or_default();
+                    if !is_valid_ident(stem) {
+                        emitter.emit(Rich::custom(
+                            span,
+                            format!("can't name a module {:?}, give it a name with `as'", stem),
+                        ));
+                    }
+                    Ident::new(stem)
+                }
+            };
+            Decl::Import(ImportDecl { span, path, name })
+        })
+        .labelled("import".into());
+
+    let r#use = just(Token::Keyword(Keyword::Use))
+        .ignore_then(ident())
+        .then_ignore(just(Token::Ctrl(Ctrl::Period)))
+        .then(ident())
+        .then(just(Token::Keyword(Keyword::As)).ignore_then(ident()).or_not())
+        .map_with_span(|((module, item), name), span| {
+            Decl::Use(UseDecl {
+                span,
+                module,
+                name: name.unwrap_or_else(|| item.clone()),
+                item,
+            })
+        })
+        .labelled("use declaration".into());
+
+    r#fn.or(import).or(r#use)
 }
 
 pub fn expr<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, ParserExtra<'s>>>) -> Parser!['s, Expr] {
     recursive(|expr| {
         let inline_expr = recursive(|inline_expr| {
             let value = select! {
-                Token::Bool(x) => Expr::Lit(Lit::Bool(x)),
-                Token::Char(x) => Expr::Lit(Lit::Char(x)),
-                Token::Int(x) => Expr::Lit(Lit::Int(x)),
-                Token::Float(x) => Expr::Lit(Lit::Float(x)),
-                Token::Str(x) => Expr::Lit(Lit::Str(x)),
+                Token::Bool(x) => ExprKind::Lit(Lit::Bool(x)),
+                Token::Char(x) => ExprKind::Lit(Lit::Char(x)),
+                Token::Int(x, suffix) => ExprKind::Lit(Lit::Int(x as i128, suffix)),
+                Token::Float(x, suffix) => ExprKind::Lit(Lit::Float(x, suffix)),
+                Token::Str(x) => ExprKind::Lit(Lit::Str(x)),
             }
+            .map_with_span(Expr::new)
             .labelled("literal".into());
 
             let ident = ident();
@@ -262,20 +394,21 @@ pub fn expr<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
                     .map_with_span(|x, span| (ObjectPropName::Expr(x), span)))
                 .then_ignore(just(Token::Ctrl(Ctrl::Colon)))
                 .then(expr.clone())
+                .map_with_span(|((name, name_span), value), span| (name_span, ObjectProp { span, name, value }))
                 .separated_by(just(Token::Ctrl(Ctrl::Comma)))
                 .allow_trailing()
                 .collect::<Vec<_>>()
                 .validate(|x, _, emitter| {
                     let mut props = Vec::new();
                     let mut duplicates = HashMap::new();
-                    for ((name, span), expr) in x {
-                        if let ObjectPropName::Ident(name_ident) = &name {
+                    for (name_span, prop) in x {
+                        if let ObjectPropName::Ident(name_ident) = &prop.name {
                             duplicates
                                 .entry(name_ident.clone())
                                 .or_insert_with(SmallVec::<[SimpleSpan; 3]>::new)
-                                .push(span);
+                                .push(name_span);
                         }
-                        props.push((name, expr));
+                        props.push(prop);
                     }
                     for (name, spans) in duplicates {
                         if spans.len() > 1 {
@@ -298,42 +431,169 @@ pub fn expr<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
                             emitter.emit(err);
                         }
                     }
-                    Expr::Object { props }
+                    ExprKind::Object { props }
                 })
                 .delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace)))
+                .map_with_span(Expr::new)
                 // don't use recovery here in case this is a block expression
                 .labelled("object".into());
 
-            let var = ident.clone().map(|name| Expr::Var { name }).labelled("variable".into());
+            let var = ident
+                .clone()
+                .map_with_span(|name, span| Expr::new(ExprKind::Var { name }, span))
+                .labelled("variable".into());
 
-            let exprs = expr
+            let array = expr
                 .clone()
                 .separated_by(just(Token::Ctrl(Ctrl::Comma)))
                 .allow_trailing()
-                .collect::<Vec<_>>();
-
-            let atom = value
-                .or(object)
-                .or(var)
-                .or(inline_expr.delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen))));
-
-            let dot = atom.foldl(
-                just(Token::Ctrl(Ctrl::Period)).ignore_then(ident.clone()).repeated(),
-                |left, right| Expr::Dot {
-                    left: Box::new(left),
-                    right,
-                },
-            );
+                .collect::<Vec<_>>()
+                .delimited_by(
+                    just(Token::Ctrl(Ctrl::LeftBracket)),
+                    just(Token::Ctrl(Ctrl::RightBracket)),
+                )
+                .map_with_span(|items, span| Expr::new(ExprKind::Array { items }, span))
+                .recover_with(via_parser(nested_recovery::<
+                    { Ctrl::LeftBracket },
+                    { Ctrl::RightBracket },
+                >()))
+                .labelled("array".into());
+
+            // `(x)` is a parenthesized expression, but `()`, `(x,)` and `(x, y)` are tuples
+            let tuple = inline_expr
+                .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+                .collect::<Vec<_>>()
+                .then(just(Token::Ctrl(Ctrl::Comma)).or_not())
+                .delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen)))
+                .map_with_span(|(mut items, trailing_comma), span| {
+                    if items.is_empty() {
+                        Expr::new(ExprKind::Lit(Lit::Unit), span)
+                    } else if items.len() == 1 && trailing_comma.is_none() {
+                        items.remove(0)
+                    } else {
+                        Expr::new(ExprKind::Tuple { items }, span)
+                    }
+                })
+                .labelled("tuple".into());
+
+            let interpolated = select! { Token::Str(x) => InterpolatedPart::Str(x) }
+                .or(expr
+                    .clone()
+                    .delimited_by(just(Token::InterpolationStart), just(Token::InterpolationEnd))
+                    .map(InterpolatedPart::Expr))
+                .repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::StrStart), just(Token::StrEnd))
+                .map_with_span(|parts, span| Expr::new(ExprKind::Interpolated { parts }, span))
+                .labelled("string interpolation".into());
+
+            let lambda_args = just(Token::Ctrl(Ctrl::Ellipsis))
+                .or_not()
+                .then(ident.clone())
+                .then(just(Token::Ctrl(Ctrl::Colon)).ignore_then(ident.clone()).or_not())
+                .map_with_span(|((variadic, name), ty), span| FnArg {
+                    span,
+                    name,
+                    ty: ty.unwrap_or_else(|| Ident::new("any")),
+                    default: None,
+                    variadic: variadic.is_some(),
+                })
+                .labelled("lambda argument".into())
+                .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .validate(|args, span, emitter| {
+                    if has_misplaced_variadic(&args) {
+                        emitter.emit(Rich::custom(span, "only the last argument can be variadic"));
+                    }
+                    args
+                });
+
+            // `fn(a, b) = a + b` or `fn(a, b) { a + b }`, which unlike `|a, b| a + b`
+            // can't be mistaken for a bitwise or when it starts a line
+            let lambda = just(Token::Keyword(Keyword::Fn))
+                .ignore_then(
+                    lambda_args.delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen))),
+                )
+                .then(
+                    stmts
+                        .clone()
+                        .delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace)))
+                        .or(just(Token::Ctrl(Ctrl::Eq))
+                            .ignore_then(expr.clone())
+                            .map(|x| vec![Stmt::from(x)])),
+                )
+                .map_with_span(|(args, body), span| {
+                    let decl = Box::new(FnDecl {
+                        span,
+                        docs: Vec::new(),
+                        modifiers: Modifiers::empty(),
+                        name: Ident::new("<lambda>"),
+                        args,
+                        return_ty: None,
+                        body: Some(body),
+                    });
+                    Expr::new(ExprKind::Lambda { decl }, span)
+                })
+                .labelled("lambda".into());
+
+            let atom = value.or(interpolated).or(lambda).or(object).or(var).or(array).or(tuple);
 
-            let call = dot.foldl(
-                exprs
+            // `f(a, b)` or `f(x: a, y: b)`
+            let call_args = ident
+                .clone()
+                .then_ignore(just(Token::Ctrl(Ctrl::Colon)))
+                .or_not()
+                .then(expr.clone())
+                .map_with_span(|(name, value), span| CallArg { span, name, value })
+                .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .validate(|args, span, emitter| {
+                    if let Some(i) = args.iter().position(|x| x.name.is_some())
+                        && args[i..].iter().any(|x| x.name.is_none())
+                    {
+                        emitter.emit(Rich::custom(
+                            span,
+                            "positional arguments must come before named arguments",
+                        ));
+                    }
+                    args
+                });
+
+            let postfix = just(Token::Ctrl(Ctrl::Period))
+                .ignore_then(ident.clone())
+                .map(Postfix::Dot)
+                .or(call_args
                     .delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen)))
-                    .repeated(),
-                |target, args| Expr::Call {
-                    target: Box::new(target),
-                    args,
-                },
-            );
+                    .map(Postfix::Call))
+                .or(expr
+                    .clone()
+                    .delimited_by(
+                        just(Token::Ctrl(Ctrl::LeftBracket)),
+                        just(Token::Ctrl(Ctrl::RightBracket)),
+                    )
+                    .map(Postfix::Index))
+                .map_with_span(|postfix, span| (postfix, span));
+
+            let call = atom.foldl(postfix.repeated(), |left, (postfix, span)| {
+                let span = SimpleSpan::new(left.span.start, span.end);
+                let kind = match postfix {
+                    Postfix::Dot(right) => ExprKind::Dot {
+                        left: Box::new(left),
+                        right,
+                    },
+                    Postfix::Call(args) => ExprKind::Call {
+                        target: Box::new(left),
+                        args,
+                    },
+                    Postfix::Index(index) => ExprKind::Index {
+                        target: Box::new(left),
+                        index: Box::new(index),
+                    },
+                };
+                Expr::new(kind, span)
+            });
 
             let pow = binary_ops!(call, [BinaryOp::Pow]);
 
@@ -342,21 +602,27 @@ pub fn expr<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
                 .or(just(Token::Op(BinaryOp::Sub)).to(UnaryOp::Minus))
                 .or(just(Token::UnaryOp(UnaryOp::Not)).to(UnaryOp::Not))
                 .or(just(Token::UnaryOp(UnaryOp::BitNot)).to(UnaryOp::BitNot))
+                .map_with_span(|op, span| (op, span))
                 .repeated()
                 .collect::<Vec<_>>()
                 .then(pow)
-                .map(|(ops, pow)| {
+                .map_with_span(|(mut ops, mut pow), span| {
+                    if let Some(&(UnaryOp::Minus, minus)) = ops.last()
+                        && let Some(lit) = negate_lit(&pow)
+                    {
+                        ops.pop();
+                        pow = Expr::new(ExprKind::Lit(lit), (minus.start..pow.span.end).into());
+                    }
+                    let ops = ops.into_iter().map(|(op, _)| op).collect::<Vec<_>>();
                     if !ops.is_empty() {
-                        Expr::UnaryOp {
-                            ops,
-                            input: Box::new(pow),
-                        }
+                        let input = Box::new(pow);
+                        Expr::new(ExprKind::UnaryOp { ops, input }, span)
                     } else {
                         pow
                     }
                 });
 
-            binary_ops!(
+            let binary = binary_ops!(
                 unary,
                 [BinaryOp::Mul, BinaryOp::Div, BinaryOp::Rem],
                 [BinaryOp::Add, BinaryOp::Sub],
@@ -375,52 +641,335 @@ pub fn expr<'s>(stmts: Recursive<Direct<'s, 's, ParserInput<'s>, Vec<Stmt>, Pars
                 ],
                 [BinaryOp::And],
                 [BinaryOp::Or],
-            )
+            );
+
+            binary
+                .clone()
+                .then(
+                    just(Token::Ctrl(Ctrl::DotDot))
+                        .to(false)
+                        .or(just(Token::Ctrl(Ctrl::DotDotEq)).to(true))
+                        .then(binary)
+                        .or_not(),
+                )
+                .map_with_span(|(start, range), span| match range {
+                    Some((inclusive, end)) => Expr::new(
+                        ExprKind::Range {
+                            start: Box::new(start),
+                            end: Box::new(end),
+                            inclusive,
+                        },
+                        span,
+                    ),
+                    None => start,
+                })
         });
 
         let block = stmts.delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace)));
 
         let expr_block = block
             .clone()
-            .map(|body| Expr::Block { body })
+            .map_with_span(|body, span| Expr::new(ExprKind::Block { body }, span))
             .recover_with(via_parser(
                 nested_recovery::<{ Ctrl::LeftBrace }, { Ctrl::RightBrace }>(),
             ))
             .labelled("block expression".into());
 
         let stmts_block = block.clone().recover_with(via_parser(
-            nested_recovery::<{ Ctrl::LeftBrace }, { Ctrl::RightBrace }>().to(vec![Stmt::Error]),
+            nested_recovery::<{ Ctrl::LeftBrace }, { Ctrl::RightBrace }>()
+                .map(|x| vec![Stmt::new(StmtKind::Error, x.span)]),
         ));
 
         let if_block = just(Token::Keyword(Keyword::If))
             .ignore_then(expr.clone())
             .then(stmts_block.clone())
             .then(just(Token::Keyword(Keyword::Else)).ignore_then(expr.clone()).or_not())
-            .map(|((condition, body), r#else)| Expr::If {
-                condition: Box::new(condition),
-                body,
-                r#else: r#else.map(Box::new),
+            .map_with_span(|((condition, body), r#else), span| {
+                let kind = ExprKind::If {
+                    condition: Box::new(condition),
+                    body,
+                    r#else: r#else.map(Box::new),
+                };
+                Expr::new(kind, span)
             })
             .labelled("if block".into());
 
         let loop_block = just(Token::Keyword(Keyword::Loop))
             .ignore_then(stmts_block.clone())
-            .map(|body| Expr::Loop { body })
+            .map_with_span(|body, span| Expr::new(ExprKind::Loop { body }, span))
             .labelled("loop block".into());
 
         let while_block = just(Token::Keyword(Keyword::While))
             .ignore_then(expr.clone())
             .then(stmts_block.clone())
-            .map(|(condition, body)| Expr::While {
-                condition: Box::new(condition),
-                body,
+            .map_with_span(|(condition, body), span| {
+                let condition = Box::new(condition);
+                Expr::new(ExprKind::While { condition, body }, span)
             })
             .labelled("while block".into());
 
-        if_block.or(loop_block).or(while_block).or(inline_expr).or(expr_block)
+        let for_block = just(Token::Keyword(Keyword::For))
+            .ignore_then(ident())
+            .then_ignore(just(Token::Keyword(Keyword::In)))
+            .then(expr.clone())
+            .then(stmts_block.clone())
+            .map_with_span(|((binding, iterable), body), span| {
+                let kind = ExprKind::For {
+                    binding,
+                    iterable: Box::new(iterable),
+                    body,
+                };
+                Expr::new(kind, span)
+            })
+            .labelled("for block".into());
+
+        let try_block = just(Token::Keyword(Keyword::Try))
+            .ignore_then(stmts_block.clone())
+            .then_ignore(just(Token::Keyword(Keyword::Catch)))
+            .then(ident())
+            .then(stmts_block.clone())
+            .map_with_span(|((body, binding), handler), span| {
+                let kind = ExprKind::Try { body, binding, handler };
+                Expr::new(kind, span)
+            })
+            .labelled("try block".into());
+
+        let match_arm = pattern()
+            .then(just(Token::Keyword(Keyword::If)).ignore_then(expr.clone()).or_not())
+            .then_ignore(just(Token::Ctrl(Ctrl::FatArrow)))
+            .then(expr.clone())
+            .map(|((pattern, guard), body)| MatchArm { pattern, guard, body })
+            .labelled("match arm".into());
+
+        let match_block = just(Token::Keyword(Keyword::Match))
+            .ignore_then(expr.clone())
+            .then(
+                match_arm
+                    .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+                    .allow_trailing()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace))),
+            )
+            .validate(|(value, arms), span, emitter| {
+                if let Some(missing) = missing_pattern(&arms) {
+                    emitter.emit(Rich::custom(
+                        span,
+                        format!("non-exhaustive patterns: `{}' not covered", missing),
+                    ));
+                }
+                let value = Box::new(value);
+                Expr::new(ExprKind::Match { value, arms }, span)
+            })
+            .labelled("match block".into());
+
+        if_block
+            .or(match_block)
+            .or(loop_block)
+            .or(while_block)
+            .or(for_block)
+            .or(try_block)
+            .or(inline_expr)
+            .or(expr_block)
+    })
+}
+
+/// Folds a `-` into the number literal it negates, so that `-128i8` is one
+/// literal rather than an out of range `128i8` being negated.
+fn negate_lit(expr: &Expr) -> Option<Lit> {
+    match &expr.kind {
+        ExprKind::Lit(Lit::Int(x, ty)) => Some(Lit::Int(-x, *ty)),
+        ExprKind::Lit(Lit::Float(x, ty)) => Some(Lit::Float(-x, *ty)),
+        _ => None,
+    }
+}
+
+pub fn pattern<'s>() -> Parser!['s, Pattern] {
+    recursive(|pattern| {
+        let lit = select! {
+            Token::Bool(x) => ExprKind::Lit(Lit::Bool(x)),
+            Token::Char(x) => ExprKind::Lit(Lit::Char(x)),
+            Token::Int(x, suffix) => ExprKind::Lit(Lit::Int(x as i128, suffix)),
+            Token::Float(x, suffix) => ExprKind::Lit(Lit::Float(x, suffix)),
+            Token::Str(x) => ExprKind::Lit(Lit::Str(x)),
+        }
+        .map_with_span(Expr::new)
+        .labelled("literal".into());
+
+        let signed_lit = just(Token::Op(BinaryOp::Sub))
+            .or_not()
+            .then(lit)
+            .validate(|(minus, lit), span, emitter| match minus {
+                Some(_) => match negate_lit(&lit) {
+                    Some(negated) => Expr::new(ExprKind::Lit(negated), span),
+                    None => {
+                        emitter.emit(Rich::custom(span, "only numbers can be negated"));
+                        lit
+                    }
+                },
+                None => lit,
+            });
+
+        let lit_or_range = signed_lit
+            .clone()
+            .then(
+                just(Token::Ctrl(Ctrl::DotDot))
+                    .to(false)
+                    .or(just(Token::Ctrl(Ctrl::DotDotEq)).to(true))
+                    .then(signed_lit)
+                    .or_not(),
+            )
+            .map(|(start, range)| match range {
+                Some((inclusive, end)) => Pattern::Range { start, end, inclusive },
+                None => Pattern::Lit(start),
+            });
+
+        let binding = ident()
+            .map(|name| match name.id.as_str() {
+                "_" => Pattern::Wildcard,
+                _ => Pattern::Binding(name),
+            })
+            .labelled("binding".into());
+
+        // `{ name, hp }` is shorthand for `{ name: name, hp: hp }`
+        let object = ident()
+            .then(just(Token::Ctrl(Ctrl::Colon)).ignore_then(pattern.clone()).or_not())
+            .map(|(name, pattern)| {
+                let pattern = pattern.unwrap_or_else(|| Pattern::Binding(name.clone()));
+                (name, pattern)
+            })
+            .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::Ctrl(Ctrl::LeftBrace)), just(Token::Ctrl(Ctrl::RightBrace)))
+            .map(|props| Pattern::Object { props })
+            .labelled("object pattern".into());
+
+        let items = pattern
+            .clone()
+            .map(PatternItem::Item)
+            .or(just(Token::Ctrl(Ctrl::Ellipsis))
+                .ignore_then(binding.clone().or_not())
+                .map(|x| PatternItem::Rest(Box::new(x.unwrap_or(Pattern::Wildcard)))))
+            .separated_by(just(Token::Ctrl(Ctrl::Comma)))
+            .collect::<Vec<_>>()
+            .then(just(Token::Ctrl(Ctrl::Comma)).or_not())
+            .validate(|(items, trailing_comma), span, emitter| {
+                let mut patterns = Vec::with_capacity(items.len());
+                let mut rest = None;
+                for item in items {
+                    match item {
+                        PatternItem::Item(x) if rest.is_none() => patterns.push(x),
+                        PatternItem::Rest(x) if rest.is_none() => rest = Some(x),
+                        _ => {
+                            emitter.emit(Rich::custom(span, "the rest pattern must come last"));
+                            break;
+                        }
+                    }
+                }
+                (patterns, rest, trailing_comma.is_some())
+            });
+
+        let array = items
+            .clone()
+            .delimited_by(
+                just(Token::Ctrl(Ctrl::LeftBracket)),
+                just(Token::Ctrl(Ctrl::RightBracket)),
+            )
+            .map(|(items, rest, _)| Pattern::Array { items, rest })
+            .labelled("array pattern".into());
+
+        // same as tuple expressions, `(x)` is just `x`
+        let tuple = items
+            .delimited_by(just(Token::Ctrl(Ctrl::LeftParen)), just(Token::Ctrl(Ctrl::RightParen)))
+            .map_with_span(|(mut items, rest, trailing_comma), span| {
+                if items.is_empty() && rest.is_none() {
+                    Pattern::Lit(Expr::new(ExprKind::Lit(Lit::Unit), span))
+                } else if items.len() == 1 && rest.is_none() && !trailing_comma {
+                    items.remove(0)
+                } else {
+                    Pattern::Tuple { items, rest }
+                }
+            })
+            .labelled("tuple pattern".into());
+
+        lit_or_range
+            .or(binding)
+            .or(object)
+            .or(array)
+            .or(tuple)
+            .labelled("pattern".into())
     })
 }
 
+fn has_misplaced_variadic(args: &[FnArg]) -> bool {
+    args.iter().rev().skip(1).any(|x| x.variadic)
+}
+
+/// Finds a pattern that no arm covers. Only bools can be covered by
+/// listing their values, so any other match needs an unguarded `_` or
+/// binding arm.
+fn missing_pattern(arms: &[MatchArm]) -> Option<String> {
+    let catch_all =
+        |arm: &MatchArm| arm.guard.is_none() && matches!(arm.pattern, Pattern::Wildcard | Pattern::Binding(_));
+    if arms.iter().any(catch_all) {
+        return None;
+    }
+
+    let mut covered = [false; 2];
+    for arm in arms {
+        let Pattern::Lit(Expr {
+            kind: ExprKind::Lit(Lit::Bool(x)),
+            ..
+        }) = arm.pattern
+        else {
+            return Some("_".into());
+        };
+        if arm.guard.is_none() {
+            covered[x as usize] = true;
+        }
+    }
+    match covered {
+        _ if arms.is_empty() => Some("_".into()),
+        [false, _] => Some("false".into()),
+        [_, false] => Some("true".into()),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+enum PatternItem {
+    Item(Pattern),
+    Rest(Box<Pattern>),
+}
+
+#[derive(Clone)]
+enum Postfix {
+    Dot(Ident),
+    Call(Vec<CallArg>),
+    Index(Expr),
+}
+
+fn doc_comments<'s>() -> Parser!['s, Vec<String>] {
+    select! {
+        Token::DocComment(x) => x,
+    }
+    .repeated()
+    .collect()
+}
+
+/// Parses an optional `pub` or `priv` modifier.
+fn visibility<'s>() -> Parser!['s, Modifiers] {
+    just(Token::Keyword(Keyword::Pub))
+        .to(Modifiers::PUB)
+        .or(just(Token::Keyword(Keyword::Priv)).to(Modifiers::PRIV))
+        .or_not()
+        .map(|x| x.unwrap_or(Modifiers::empty()))
+}
+
+fn is_valid_ident(x: &str) -> bool {
+    x.starts_with(|c: char| c.is_alphabetic() || c == '_') && x.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 fn ident<'s>() -> Parser!['s, Ident] {
     select! {
         Token::Ident(x) => x,
@@ -429,7 +978,8 @@ fn ident<'s>() -> Parser!['s, Ident] {
 }
 
 fn binary_op<const O: BinaryOp>(left: Box<Expr>, right: Box<Expr>) -> Expr {
-    Expr::BinaryOp { op: O, left, right }
+    let span = SimpleSpan::new(left.span.start, right.span.end);
+    Expr::new(ExprKind::BinaryOp { op: O, left, right }, span)
 }
 
 macro binary_ops($atom:expr, [$($op:expr),+$(,)?]$(,)?$(,$($rest:tt)+)?) {{
@@ -478,6 +1028,6 @@ fn nested_recovery<'s, const LEFT: Ctrl, const RIGHT: Ctrl>() -> Parser!['s, Exp
         Token::Ctrl(LEFT),
         Token::Ctrl(RIGHT),
         other_closers::<LEFT, RIGHT>(),
-        |_| Expr::Error,
+        |span| Expr::new(ExprKind::Error, span),
     )
 }
@@ -0,0 +1,80 @@
+use crate::parse::ast::{Decl, Expr, ExprKind, Program, StmtKind};
+use crate::parse::MAX_NESTING_DEPTH;
+use crate::Engine;
+
+fn parse(src: &str) -> Program {
+    match Engine::new().compile(src, "test.nir") {
+        Ok(script) => script.program,
+        Err(diagnostics) => panic!("{:?} should compile, but got:\n{}", src, diagnostics),
+    }
+}
+
+#[test]
+fn doc_comments_are_kept_on_declarations() {
+    let program = parse("/// adds one\nfn succ(x: any) = x + 1\n/// the answer\nlet answer = 42");
+    let [StmtKind::Decl(Decl::FnDecl(succ)), StmtKind::Decl(Decl::LetDecl(answer))] =
+        &program.stmts.iter().map(|x| &x.kind).collect::<Vec<_>>()[..]
+    else {
+        panic!("expected a function and a variable, got {:?}", program.stmts);
+    };
+    assert_eq!(succ.docs.len(), 1);
+    assert_eq!(answer.docs.len(), 1);
+}
+
+#[test]
+fn doc_comments_are_ignored_before_other_statements() {
+    let program = parse(
+        "let x = 1
+        /// an assignment
+        x = 2
+        /// an expression
+        x + 1
+        /// an import
+        import \"other.nir\"
+        /// a use
+        use other.item
+        loop {
+            /// a break
+            break
+            /// the end of a block
+        }
+        /// the end of a file",
+    );
+    assert_eq!(program.stmts.len(), 6);
+}
+
+#[test]
+fn deeply_nested_programs_are_rejected() {
+    let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    parse(&nested(MAX_NESTING_DEPTH));
+    let err = Engine::new()
+        .compile(&nested(MAX_NESTING_DEPTH + 1), "test.nir")
+        .unwrap_err();
+    assert!(err.to_string().contains("nested more than"));
+}
+
+#[test]
+fn lambdas_can_start_a_line() {
+    let program = parse("let x = 1\nfn(y) = x + y\nfn() { x }");
+    assert_eq!(program.stmts.len(), 3);
+    assert!(program.stmts[1..].iter().all(|x| matches!(
+        &x.kind,
+        StmtKind::Expr(Expr {
+            kind: ExprKind::Lambda { .. },
+            ..
+        })
+    )));
+}
+
+#[test]
+fn matches_need_an_arm_for_every_value() {
+    parse("match 1 { 0 => 1, _ => 2 }");
+    parse("match 1 { 0 => 1, x => x }");
+    parse("match true { true => 1, false => 0 }");
+
+    let missing = |src| Engine::new().compile(src, "test.nir").unwrap_err().to_string();
+    assert!(missing("match 1 { 0 => 1, 1 => 2 }").contains("`_' not covered"));
+    assert!(missing("match 1 { 0 => 1, x if x > 1 => x }").contains("`_' not covered"));
+    assert!(missing("match 1 { }").contains("`_' not covered"));
+    assert!(missing("match true { true => 1 }").contains("`false' not covered"));
+}
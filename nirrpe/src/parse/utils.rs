@@ -64,6 +64,23 @@ pub fn just_str<'s>(s: &'static str) -> Lexer!['s, &'s str] {
     })
 }
 
+/// Like [`just_str`], but only matches if `s` makes up an entire identifier,
+/// so that `just_word("in")` matches `in` but not the start of `index`.
+#[must_use]
+pub fn just_word<'s>(s: &'static str) -> Lexer!['s, &'s str] {
+    chumsky::text::unicode::ident().try_map(move |x: &str, span| {
+        if x == s {
+            Ok(x)
+        } else {
+            Err(chumsky::error::Error::<&'s str>::expected_found(
+                s.chars().map(|x| Some(MaybeRef::Val(x))).collect::<Vec<_>>(),
+                x.chars().next().map(MaybeRef::Val),
+                span,
+            ))
+        }
+    })
+}
+
 #[must_use]
 pub fn recover_delimited_by<'a, C, I, E>(start: C, end: C) -> impl Parser<'a, I, (), E> + Clone
 where
@@ -0,0 +1,246 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use crate::runtime::modules::Module;
+use crate::runtime::value::{Function, Object, Value};
+use crate::runtime::Scope;
+
+/// How many values are tracked before the first collection.
+const INITIAL_THRESHOLD: usize = 1024;
+
+/// Finds reference cycles that reference counting alone would leak, like a
+/// function stored in the scope it was defined in.
+///
+/// Every cycle goes through a function, object or module, so only those are
+/// tracked. When collecting, everything they reference is counted, and any
+/// value with more references than that is held from outside the cycles,
+/// by a variable being executed or by the host. Whatever those can't reach
+/// is garbage, and is emptied to break its cycles.
+pub struct Heap {
+    tracked: RefCell<Vec<WeakNode>>,
+    /// How many values can be tracked before collecting again.
+    threshold: Cell<usize>,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            tracked: RefCell::new(Vec::new()),
+            threshold: Cell::new(INITIAL_THRESHOLD),
+        }
+    }
+
+    /// Tracks a value if it's a function, object or module.
+    pub fn track(&self, value: &Value) {
+        let node = match value {
+            Value::Function(x) => WeakNode::Function(Rc::downgrade(x)),
+            Value::Object(x) => WeakNode::Object(Rc::downgrade(x)),
+            Value::Module(x) => WeakNode::Module(Rc::downgrade(x)),
+            _ => return,
+        };
+        self.tracked.borrow_mut().push(node);
+    }
+
+    /// Whether enough values were tracked since the last collection to
+    /// make another worthwhile.
+    pub fn should_collect(&self) -> bool {
+        self.tracked.borrow().len() >= self.threshold.get()
+    }
+
+    /// Frees every cycle that can't be reached from outside the heap.
+    pub fn collect(&self) {
+        let mut entries = self.reachable();
+
+        // whatever is referenced more than the heap itself references it is
+        // held from outside
+        for entry in entries.values_mut() {
+            entry.refs = entry.node.strong_count() - 1;
+        }
+        let edges = entries.values().flat_map(|x| x.children.clone()).collect::<Vec<_>>();
+        for child in edges {
+            if let Some(entry) = entries.get_mut(&child) {
+                entry.refs = entry.refs.saturating_sub(1);
+            }
+        }
+
+        let mut stack = Vec::new();
+        for (id, entry) in entries.iter_mut() {
+            if entry.live || entry.refs > 0 {
+                entry.live = true;
+                stack.push(*id);
+            }
+        }
+        while let Some(id) = stack.pop() {
+            for child in entries[&id].children.clone() {
+                // everything reachable was found above
+                let child_entry = entries.get_mut(&child).unwrap();
+                if !child_entry.live {
+                    child_entry.live = true;
+                    stack.push(child);
+                }
+            }
+        }
+
+        for entry in entries.values().filter(|x| !x.live) {
+            entry.node.clear();
+        }
+    }
+
+    /// Empties every scope in the heap and then collects, for when the
+    /// runtime is dropped. Functions can't be called without their runtime,
+    /// so only the values the host still holds need to survive.
+    pub fn release(&self) {
+        for entry in self.reachable().values() {
+            if let Node::Scope(_) = entry.node {
+                entry.node.clear();
+            }
+        }
+        self.collect();
+    }
+
+    /// Finds everything reachable from the tracked values, holding exactly
+    /// one extra reference to each.
+    fn reachable(&self) -> HashMap<*const (), Entry> {
+        let roots = {
+            let mut tracked = self.tracked.borrow_mut();
+            tracked.retain(|x| x.upgrade().is_some());
+            self.threshold.set(INITIAL_THRESHOLD.max(tracked.len() * 2));
+            tracked.iter().filter_map(WeakNode::upgrade).collect::<Vec<_>>()
+        };
+
+        let mut entries = HashMap::new();
+        let mut stack = roots;
+        while let Some(node) = stack.pop() {
+            if entries.contains_key(&node.id()) {
+                continue;
+            }
+            let mut children = Vec::new();
+            let complete = node.children(&mut |child| {
+                children.push(child.id());
+                stack.push(child);
+            });
+            entries.insert(
+                node.id(),
+                Entry {
+                    node,
+                    children,
+                    refs: 0,
+                    live: !complete,
+                },
+            );
+        }
+
+        entries
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Entry {
+    node: Node,
+    children: Vec<*const ()>,
+    /// References from outside the heap.
+    refs: usize,
+    live: bool,
+}
+
+enum Node {
+    Scope(Rc<Scope>),
+    Function(Rc<Function>),
+    Object(Rc<RefCell<Object>>),
+    Module(Rc<Module>),
+}
+
+impl Node {
+    fn id(&self) -> *const () {
+        match self {
+            Node::Scope(x) => Rc::as_ptr(x) as *const (),
+            Node::Function(x) => Rc::as_ptr(x) as *const (),
+            Node::Object(x) => Rc::as_ptr(x) as *const (),
+            Node::Module(x) => Rc::as_ptr(x) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Scope(x) => Rc::strong_count(x),
+            Node::Function(x) => Rc::strong_count(x),
+            Node::Object(x) => Rc::strong_count(x),
+            Node::Module(x) => Rc::strong_count(x),
+        }
+    }
+
+    /// Calls `f` on everything this references, returning false if its
+    /// contents are borrowed and couldn't be read.
+    fn children(&self, f: &mut impl FnMut(Node)) -> bool {
+        match self {
+            Node::Scope(scope) => {
+                if let Some(parent) = &scope.parent {
+                    f(Node::Scope(parent.clone()));
+                }
+                let Ok(variables) = scope.variables.try_borrow() else {
+                    return false;
+                };
+                variables.values().for_each(|x| value_children(x, f));
+            }
+            Node::Function(function) => f(Node::Scope(function.env().clone())),
+            Node::Object(object) => {
+                let Ok(object) = object.try_borrow() else {
+                    return false;
+                };
+                object.values.values().for_each(|x| value_children(x, f));
+            }
+            Node::Module(module) => f(Node::Scope(module.scope.clone())),
+        }
+        true
+    }
+
+    /// Drops everything this holds, which breaks any cycle it's in.
+    fn clear(&self) {
+        match self {
+            Node::Scope(scope) => drop(scope.variables.take()),
+            Node::Object(object) => {
+                let values = mem::take(&mut object.borrow_mut().values);
+                drop(values);
+            }
+            // these can only be in a cycle through a scope
+            Node::Function(_) | Node::Module(_) => {}
+        }
+    }
+}
+
+fn value_children(value: &Value, f: &mut impl FnMut(Node)) {
+    match value {
+        Value::Function(x) => f(Node::Function(x.clone())),
+        Value::Object(x) => f(Node::Object(x.clone())),
+        Value::Module(x) => f(Node::Module(x.clone())),
+        Value::Array(items) | Value::Tuple(items) => items.iter().for_each(|x| value_children(x, f)),
+        Value::Range(range) => {
+            value_children(&range.start, f);
+            value_children(&range.end, f);
+        }
+        _ => {}
+    }
+}
+
+enum WeakNode {
+    Function(Weak<Function>),
+    Object(Weak<RefCell<Object>>),
+    Module(Weak<Module>),
+}
+
+impl WeakNode {
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            WeakNode::Function(x) => x.upgrade().map(Node::Function),
+            WeakNode::Object(x) => x.upgrade().map(Node::Object),
+            WeakNode::Module(x) => x.upgrade().map(Node::Module),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds and reads the modules that scripts import.
+pub trait ModuleLoader {
+    /// Resolves the path written in an `import` in the module at `importer`.
+    /// Modules that resolve to the same path are only loaded once.
+    fn resolve(&self, importer: &Path, path: &str) -> Result<PathBuf, String>;
+
+    /// Reads the source of a module at a resolved path.
+    fn load(&self, path: &Path) -> Result<String, String>;
+}
+
+/// Loads modules from files relative to the module that imports them,
+/// refusing any outside of a root directory.
+#[derive(Clone, Debug)]
+pub struct FileLoader {
+    pub root: PathBuf,
+}
+
+impl FileLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Creates a loader rooted at the directory `script` is in.
+    pub fn beside(script: &Path) -> Self {
+        match script.parent() {
+            Some(x) if !x.as_os_str().is_empty() => Self::new(x),
+            _ => Self::new("."),
+        }
+    }
+}
+
+impl ModuleLoader for FileLoader {
+    fn resolve(&self, importer: &Path, path: &str) -> Result<PathBuf, String> {
+        let path = importer.parent().unwrap_or(Path::new("")).join(path);
+        let path =
+            fs::canonicalize(&path).map_err(|err| format!("couldn't find module {}: {}", path.display(), err))?;
+        let root = fs::canonicalize(&self.root)
+            .map_err(|err| format!("couldn't find module root {}: {}", self.root.display(), err))?;
+        if !path.starts_with(&root) {
+            return Err(format!(
+                "module {} is outside of the module root {}",
+                path.display(),
+                root.display()
+            ));
+        }
+        Ok(path)
+    }
+
+    fn load(&self, path: &Path) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|err| format!("couldn't read module {}: {}", path.display(), err))
+    }
+}
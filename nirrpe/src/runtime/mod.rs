@@ -1,33 +1,120 @@
+pub mod gc;
+pub mod loader;
+pub mod modules;
+pub mod natives;
+pub mod numeric;
+pub mod patterns;
+pub mod strings;
+pub mod trace;
 pub mod utils;
 pub mod value;
 
+#[cfg(test)]
+mod tests;
+
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
 use std::rc::Rc;
 
+use chumsky::span::SimpleSpan;
+
 use crate::parse::ast::{
-    Assignment, BinaryOp, ControlFlow, Decl, Expr, Modifiers, ObjectPropName, Program, Stmt, UnaryOp,
+    Assignment, BinaryOp, ControlFlow, Decl, Expr, ExprKind, FnArg, FnDecl, InterpolatedPart, Modifiers, NumType,
+    ObjectProp, ObjectPropName, PathSegment, Stmt, StmtKind, UnaryOp,
 };
 use crate::parse::ident::Ident;
-use crate::runtime::value::{Object, Value};
+use crate::runtime::modules::{ModuleCache, ModuleInfo};
+use crate::runtime::natives::IntoNativeFunction;
+use crate::runtime::numeric::{cast, execute_numeric_binop, execute_numeric_unary_op};
+use crate::runtime::strings::execute_string_binop;
+use crate::runtime::trace::{Frame, FrameKind, Location, Panic};
+use crate::runtime::value::{checked_index, Function, Object, Range, Value};
+use crate::typeck::types::Type;
+use crate::Script;
 
-pub struct NirrpeRuntime<'r> {
-    global: Scope<'r>,
+pub struct NirrpeRuntime {
+    modules: Rc<ModuleCache>,
+    /// The global scope of the last main module executed, which is kept
+    /// even if it panicked.
+    main: Option<Rc<Scope>>,
 }
 
-impl<'r> NirrpeRuntime<'r> {
+impl NirrpeRuntime {
     pub fn new() -> Self {
         Self {
-            global: Scope::global(),
+            modules: Rc::new(ModuleCache::new(Scope::globals())),
+            main: None,
+        }
+    }
+
+    /// Executes a script as the main module, returning the value of its last statement.
+    pub fn execute(&mut self, script: &Script) -> Result<Value, Panic> {
+        let path = fs::canonicalize(&script.path).unwrap_or_else(|_| script.path.clone());
+        let scope = self
+            .modules
+            .module_scope(path, script.source.as_str().into(), script.loader.clone());
+        self.main = Some(scope.clone());
+        let result = match self.modules.execute(scope, &script.program) {
+            Ok((_, value)) => Ok(value),
+            Err(RuntimeControlFlow::Panic(panic)) => Err(*panic),
+            Err(_) => unreachable!("other control flow is turned into panics at the top level of a module"),
+        };
+        self.modules.heap.collect();
+        result
+    }
+
+    /// Gets a variable declared at the top level of the last main module
+    /// executed, or a global variable if it has none with that name.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match &self.main {
+            Some(main) => main.get_value(&Ident::new(name)),
+            None => self.get_global(name),
         }
     }
 
-    pub fn execute(&mut self, program: Program) {
-        program.execute(&mut self.global);
+    /// Gets a global variable, which every module can see.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.modules.globals.get_value(&Ident::new(name))
+    }
+
+    /// Registers a Rust function that scripts can call by declaring an
+    /// `extern fn` with the same name, replacing any function already
+    /// registered under that name.
+    ///
+    /// ```no_run
+    /// # use nirrpe::Runtime;
+    /// let mut runtime = Runtime::new();
+    /// runtime.register("roll_dice", |sides: u64| sides.min(4));
+    /// ```
+    pub fn register<Args>(&mut self, name: &str, fun: impl IntoNativeFunction<Args>) {
+        self.modules.natives.borrow_mut().register(name, fun);
+    }
+
+    /// Registers a Rust function that takes any number of arguments.
+    pub fn register_variadic(&mut self, name: &str, fun: impl Fn(Vec<Value>) -> Result<Value, Panic> + 'static) {
+        self.modules.natives.borrow_mut().register_variadic(name, fun);
+    }
+
+    /// Sets how deeply function calls can be nested before panicking with a
+    /// stack overflow, which is [`DEFAULT_MAX_CALL_DEPTH`](modules::DEFAULT_MAX_CALL_DEPTH) by default.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.modules.max_depth.set(depth);
+    }
+
+    /// Sets a global variable, which every module can see and assign to.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.modules.globals.define(Ident::new(name), value);
     }
 }
 
-impl<'r> Default for NirrpeRuntime<'r> {
+impl Drop for NirrpeRuntime {
+    fn drop(&mut self) {
+        self.modules.heap.release();
+    }
+}
+
+impl Default for NirrpeRuntime {
     fn default() -> Self {
         Self::new()
     }
@@ -38,43 +125,100 @@ pub enum RuntimeControlFlow {
     Continue,
     Break(Value),
     Return(Value),
-    Panic(Value),
+    Panic(Box<Panic>),
+}
+
+impl From<Panic> for RuntimeControlFlow {
+    fn from(panic: Panic) -> Self {
+        Self::Panic(Box::new(panic))
+    }
+}
+
+impl RuntimeControlFlow {
+    pub fn panic(value: Value) -> Self {
+        Self::Panic(Box::new(Panic::new(value)))
+    }
+
+    /// Records where a panic happened, unless a more specific location was
+    /// already recorded.
+    fn located_at(mut self, span: SimpleSpan, scope: &Scope) -> Self {
+        if let Self::Panic(panic) = &mut self && panic.location.is_none() {
+            panic.location = Some(Location::new(span, scope));
+        }
+        self
+    }
+
+    /// Adds a call or import made at `span` to the trace of a panic.
+    fn called_from(mut self, kind: FrameKind, span: SimpleSpan, scope: &Scope) -> Self {
+        if let Self::Panic(panic) = &mut self {
+            let location = Location::new(span, scope);
+            panic.trace.push(Frame { kind, location });
+        }
+        self
+    }
 }
 
 pub macro runtime_panic {
     ($msg:literal) => {
-        return ::core::result::Result::Err(
-            $crate::runtime::RuntimeControlFlow::Panic(
+        return ::core::result::Result::Err(::core::convert::From::from(
+            $crate::runtime::trace::Panic::new(
                 $crate::runtime::value::Value::Str($msg.to_string())
             )
-        )
+        ))
     },
     ($msg:literal, $($args:tt)*) => {
-        return ::core::result::Result::Err(
-            $crate::runtime::RuntimeControlFlow::Panic(
+        return ::core::result::Result::Err(::core::convert::From::from(
+            $crate::runtime::trace::Panic::new(
                 $crate::runtime::value::Value::Str(::std::format!($msg, $($args)*))
             )
-        )
+        ))
     },
 }
 
-pub struct Scope<'p> {
-    parent: Option<&'p Scope<'p>>,
+/// A set of variables, along with the scope it's nested in. Scopes are
+/// reference counted so that functions can keep the scope they were defined
+/// in alive after it's exited, and the cycles that makes are freed by the
+/// runtime's [`Heap`](gc::Heap).
+pub struct Scope {
+    parent: Option<Rc<Scope>>,
     variables: RefCell<HashMap<Ident, Value>>,
+    /// Only set on the global scope of a module.
+    module: Option<ModuleInfo>,
 }
 
-impl<'p> Scope<'p> {
-    pub fn new(parent: &'p Scope<'p>) -> Self {
-        Self {
-            parent: Some(parent),
+impl Scope {
+    pub fn new(parent: &Rc<Scope>) -> Rc<Self> {
+        Rc::new(Self {
+            parent: Some(parent.clone()),
             variables: RefCell::new(HashMap::new()),
-        }
+            module: None,
+        })
     }
 
-    pub fn global() -> Self {
-        Self {
+    /// Creates the scope holding the globals of a runtime, which every
+    /// module is nested in.
+    pub fn globals() -> Rc<Self> {
+        Rc::new(Self {
             parent: None,
             variables: RefCell::new(HashMap::new()),
+            module: None,
+        })
+    }
+
+    /// Creates the global scope of a module.
+    pub fn module(info: ModuleInfo, globals: &Rc<Scope>) -> Rc<Self> {
+        Rc::new(Self {
+            parent: Some(globals.clone()),
+            variables: RefCell::new(HashMap::new()),
+            module: Some(info),
+        })
+    }
+
+    /// Gets the module this scope is in.
+    pub fn module_info(&self) -> Option<&ModuleInfo> {
+        match &self.module {
+            Some(x) => Some(x),
+            None => self.parent.as_ref().and_then(|p| p.module_info()),
         }
     }
 
@@ -85,52 +229,57 @@ impl<'p> Scope<'p> {
     pub fn has_value(&self, name: &Ident) -> bool {
         match self.variables.borrow().contains_key(name) {
             true => true,
-            false => self.parent.map_or(false, |p| p.has_value(name)),
+            false => self.parent.as_ref().map_or(false, |p| p.has_value(name)),
         }
     }
 
     pub fn get_value(&self, name: &Ident) -> Option<Value> {
         match self.variables.borrow().get(name) {
             Some(x) => Some(x.clone()),
-            None => self.parent.and_then(|p| p.get_value(name)),
+            None => self.parent.as_ref().and_then(|p| p.get_value(name)),
         }
     }
 
+    /// Defines a variable in this scope, replacing any variable already
+    /// defined here with the same name.
+    pub fn define(&self, name: Ident, value: Value) {
+        self.variables.borrow_mut().insert(name, value);
+    }
+
     pub fn replace_value(&self, name: &Ident, value: Value) -> bool {
-        match self.variables.borrow_mut().get_mut(name) {
+        let mut variables = self.variables.borrow_mut();
+        match variables.get_mut(name) {
             Some(x) => {
                 *x = value;
                 true
             }
-            None => self.parent.map_or(false, move |p| p.replace_value(name, value)),
+            None => {
+                drop(variables);
+                self.parent
+                    .as_ref()
+                    .map_or(false, move |p| p.replace_value(name, value))
+            }
         }
     }
-}
 
-impl Program {
-    pub fn execute(&self, scope: &mut Scope) {
-        if let Err(err) = execute_stmts(&self.stmts, scope) {
-            match err {
-                RuntimeControlFlow::Panic(x) => {
-                    eprintln!("Program panicked at '{}'", x);
-                }
-                err => {
-                    eprintln!(
-                        "Illegal top-level {}",
-                        match err {
-                            RuntimeControlFlow::Continue => "continue",
-                            RuntimeControlFlow::Break(_) => "break",
-                            RuntimeControlFlow::Return(_) => "return",
-                            _ => unreachable!(),
-                        }
-                    );
-                }
-            }
+    /// Tracks a function or object created in this scope, so that cycles
+    /// it ends up in can be collected.
+    fn track(&self, value: Value) -> Value {
+        if let Some(cache) = self.module_info().and_then(|x| x.cache.upgrade()) {
+            cache.heap.track(&value);
         }
+        value
     }
 }
 
-fn execute_stmts(stmts: &Vec<Stmt>, scope: &mut Scope) -> Result<Value, RuntimeControlFlow> {
+/// Runs `f` on a new segment of stack if the current one is running out, so
+/// that deeply nested programs are limited by [`ModuleCache::max_depth`]
+/// instead of the host's stack.
+fn grow_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(256 * 1024, 4 * 1024 * 1024, f)
+}
+
+fn execute_stmts(stmts: &Vec<Stmt>, scope: &Rc<Scope>) -> Result<Value, RuntimeControlFlow> {
     let mut last_ret = Value::unit();
     for stmt in stmts {
         last_ret = stmt.execute(scope)?;
@@ -139,15 +288,22 @@ fn execute_stmts(stmts: &Vec<Stmt>, scope: &mut Scope) -> Result<Value, RuntimeC
 }
 
 impl Stmt {
-    pub fn execute(&self, scope: &mut Scope) -> Result<Value, RuntimeControlFlow> {
-        match self {
-            Stmt::Decl(decl) => match decl {
+    pub fn execute(&self, scope: &Rc<Scope>) -> Result<Value, RuntimeControlFlow> {
+        grow_stack(|| self.execute_kind(scope)).map_err(|x| x.located_at(self.span, scope))
+    }
+
+    fn execute_kind(&self, scope: &Rc<Scope>) -> Result<Value, RuntimeControlFlow> {
+        match &self.kind {
+            StmtKind::Decl(decl) => match decl {
                 Decl::LetDecl(r#let) => {
-                    if scope.has_local_value(&r#let.name) {
-                        runtime_panic!("variable {:?} already defined", r#let.name);
-                    } else {
-                        let value = r#let.value.execute(scope)?;
-                        scope.variables.borrow_mut().insert(r#let.name.clone(), value);
+                    let value = r#let.value.execute(scope)?;
+                    let mut bindings = Vec::new();
+                    r#let.pattern.bind(&value, scope, &mut bindings)?;
+                    for (name, value) in bindings {
+                        if scope.has_local_value(&name) {
+                            runtime_panic!("variable {:?} already defined", name);
+                        }
+                        scope.define(name, value);
                     }
                     Ok(Value::unit())
                 }
@@ -155,57 +311,83 @@ impl Stmt {
                     if scope.has_local_value(&function.name) {
                         runtime_panic!("function {:?} already defined", function.name);
                     } else {
-                        scope
-                            .variables
-                            .borrow_mut()
-                            .insert(function.name.clone(), Value::Function(function.clone()));
+                        let value = scope.track(Value::Function(Rc::new(Function::new(function.clone(), scope))));
+                        scope.define(function.name.clone(), value);
                     }
                     Ok(Value::unit())
                 }
+                Decl::Import(import) => {
+                    let Some(info) = scope.module_info() else {
+                        runtime_panic!("modules can only be imported from within another module");
+                    };
+                    let module = info
+                        .cache()?
+                        .import(info, &import.path)
+                        .map_err(|x| x.called_from(FrameKind::Import(import.path.clone()), import.span, scope))?;
+                    if scope.has_local_value(&import.name) {
+                        runtime_panic!("variable {:?} already defined", import.name);
+                    }
+                    scope.define(import.name.clone(), Value::Module(module));
+                    Ok(Value::unit())
+                }
+                Decl::Use(r#use) => {
+                    let value = match scope.get_value(&r#use.module) {
+                        Some(Value::Module(module)) => module.get(&r#use.item)?,
+                        Some(x) => runtime_panic!("{:?} is not a module, found {}", r#use.module, x),
+                        None => runtime_panic!("module {:?} isn't defined", r#use.module),
+                    };
+                    if scope.has_local_value(&r#use.name) {
+                        runtime_panic!("variable {:?} already defined", r#use.name);
+                    }
+                    scope.define(r#use.name.clone(), value);
+                    Ok(Value::unit())
+                }
             },
-            Stmt::Expr(expr) => expr.execute(scope),
-            Stmt::Assignment(Assignment { path, value, op }) => {
-                assert!(!path.is_empty(), "empty left hand for assignment?");
+            StmtKind::Expr(expr) => expr.execute(scope),
+            StmtKind::Assignment(Assignment {
+                target,
+                path,
+                value,
+                op,
+            }) => {
                 let mut result = value.execute(scope)?;
+                let mut keys = Vec::with_capacity(path.len());
+                for segment in path {
+                    keys.push(match segment {
+                        PathSegment::Property(ident) => PathKey::Property(ident),
+                        PathSegment::Index(index) => PathKey::Index(index.execute(scope)?),
+                    });
+                }
                 if let Some(op) = op {
                     assert!(
                         op.allows_assignment(),
                         "invalid AST: given operator {:?} is not assignable!",
                         op
                     );
-                    let mut current = match scope.get_value(&path[0]) {
+                    let mut current = match scope.get_value(target) {
                         Some(value) => value,
-                        None => runtime_panic!("variable {:?} is undefined", &path[0]),
+                        None => runtime_panic!("variable {:?} is undefined", target),
                     };
-                    for ident in &path[1..] {
-                        current = current.try_get_property(ident)?;
+                    for key in &keys {
+                        current = key.get(&current)?;
                     }
                     result = execute_builtin_binop(*op, current, result)?;
                 }
-                if path.len() == 1 {
-                    if !scope.replace_value(&path[0], result) {
-                        panic!("variable {:?} is undefined", &path[0]);
+                if keys.is_empty() {
+                    if !scope.replace_value(target, result) {
+                        runtime_panic!("variable {:?} is undefined", target);
                     }
                 } else {
-                    let mut current = match scope.get_value(&path[0]) {
+                    let mut root = match scope.get_value(target) {
                         Some(value) => value,
-                        None => runtime_panic!("variable {:?} is undefined", &path[0]),
+                        None => runtime_panic!("variable {:?} is undefined", target),
                     };
-                    for ident in &path[1..path.len() - 1] {
-                        current = current.try_get_property(ident)?;
-                    }
-                    let last_ident = &path[path.len() - 1];
-                    match current {
-                        Value::Object(object) => match object.borrow_mut().values.get_mut(&last_ident.id) {
-                            Some(x) => *x = result,
-                            None => runtime_panic!("property {:?} not found in object", last_ident),
-                        },
-                        _ => runtime_panic!("only objects can have properties"),
-                    }
+                    assign_path(target, &keys, 0, &mut root, result)?;
+                    scope.replace_value(target, root);
                 }
                 Ok(Value::unit())
             }
-            Stmt::ControlFlow(flow) => match flow {
+            StmtKind::ControlFlow(flow) => match flow {
                 ControlFlow::Continue => Err(RuntimeControlFlow::Continue),
                 ControlFlow::Break(maybe_expr) => {
                     let value = match maybe_expr {
@@ -222,18 +404,22 @@ impl Stmt {
                     Err(RuntimeControlFlow::Return(value))
                 }
             },
-            Stmt::Error => runtime_panic!("Cannot execute AST with errors!"),
+            StmtKind::Error => runtime_panic!("Cannot execute AST with errors!"),
         }
     }
 }
 
 impl Expr {
-    pub fn execute(&self, scope: &mut Scope) -> Result<Value, RuntimeControlFlow> {
-        match self {
-            Expr::Lit(lit) => Ok(lit.into()),
-            Expr::Object { props } => {
+    pub fn execute(&self, scope: &Rc<Scope>) -> Result<Value, RuntimeControlFlow> {
+        grow_stack(|| self.execute_kind(scope)).map_err(|x| x.located_at(self.span, scope))
+    }
+
+    fn execute_kind(&self, scope: &Rc<Scope>) -> Result<Value, RuntimeControlFlow> {
+        match &self.kind {
+            ExprKind::Lit(lit) => lit.try_into(),
+            ExprKind::Object { props } => {
                 let mut values = HashMap::with_capacity(props.len());
-                for (name, expr) in props {
+                for ObjectProp { name, value: expr, .. } in props {
                     let name = match name {
                         ObjectPropName::Ident(ident) => ident.id.clone(),
                         ObjectPropName::Expr(name_expr) => match name_expr.execute(scope)? {
@@ -244,66 +430,128 @@ impl Expr {
                     let value = expr.execute(scope)?;
                     values.insert(name, value);
                 }
-                Ok(Value::Object(Rc::new(RefCell::new(Object { values }))))
+                Ok(scope.track(Value::Object(Rc::new(RefCell::new(Object { values })))))
+            }
+            ExprKind::Array { items } => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(item.execute(scope)?);
+                }
+                Ok(Value::Array(values))
+            }
+            ExprKind::Tuple { items } => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(item.execute(scope)?);
+                }
+                Ok(Value::Tuple(values))
             }
-            Expr::Var { name } => match scope.get_value(name) {
+            ExprKind::Interpolated { parts } => {
+                let mut string = String::new();
+                for part in parts {
+                    match part {
+                        InterpolatedPart::Str(x) => string.push_str(x),
+                        InterpolatedPart::Expr(expr) => string.push_str(&expr.execute(scope)?.to_string()),
+                    }
+                }
+                Ok(Value::Str(string))
+            }
+            ExprKind::Var { name } => match scope.get_value(name) {
                 Some(x) => Ok(x),
                 None => runtime_panic!("variable {:?} isn't defined", name),
             },
-            Expr::Dot { left, right } => left.execute(scope)?.try_get_property(right),
-            Expr::UnaryOp { ops, input } => {
+            ExprKind::Dot { left, right } => left.execute(scope)?.try_get_property(right),
+            ExprKind::Index { target, index } => {
+                let target = target.execute(scope)?;
+                target.try_get_index(&index.execute(scope)?)
+            }
+            ExprKind::UnaryOp { ops, input } => {
                 let mut input = input.execute(scope)?;
                 for op in ops.iter().rev() {
                     input = execute_builtin_unary_op(*op, input)?;
                 }
                 Ok(input)
             }
-            Expr::BinaryOp { op, left, right } => {
+            ExprKind::BinaryOp { op, left, right } => {
                 let left = left.execute(scope)?;
                 let right = right.execute(scope)?;
                 execute_builtin_binop(*op, left, right)
             }
-            Expr::Call { target, args } => {
-                let decl = match target.execute(scope)? {
-                    Value::Function(decl) => decl,
+            ExprKind::Lambda { decl } => {
+                let function = Function::new(decl.as_ref().clone(), scope);
+                Ok(scope.track(Value::Function(Rc::new(function))))
+            }
+            ExprKind::Call { target, args } => {
+                let function = match target.execute(scope)? {
+                    Value::Function(function) => function,
                     _ => runtime_panic!("tried to call a non-function"),
                 };
+                let decl = &function.decl;
 
                 let fun_name = &decl.name;
-                if decl.args.len() != args.len() {
-                    runtime_panic!("wrong number of arguments to function {:?}", fun_name);
-                }
-
-                let mut evaluated_args = HashMap::new();
-                for arg in args.iter().enumerate() {
-                    evaluated_args.insert(decl.args[arg.0].name.clone(), arg.1.execute(scope)?);
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push((arg.name.as_ref(), arg.value.execute(scope)?));
                 }
 
+                let _call = match scope.module_info() {
+                    Some(info) => Some(info.cache()?.enter_call()?),
+                    None => None,
+                };
+                let new_scope = Scope::new(function.env());
+                bind_args(decl, values, &new_scope)?;
                 if let Some(stmts) = &decl.body {
-                    let mut new_scope = Scope::new(scope);
-                    evaluated_args.into_iter().for_each(|(k, v)| {
-                        new_scope.variables.borrow_mut().insert(k, v);
-                    });
-                    match execute_stmts(stmts, &mut new_scope) {
-                        Ok(x) | Err(RuntimeControlFlow::Return(x)) => Ok(x),
+                    let value = match execute_stmts(stmts, &new_scope) {
+                        Ok(x) | Err(RuntimeControlFlow::Return(x)) => x,
                         Err(RuntimeControlFlow::Continue) => runtime_panic!("Illegal continue outside loop"),
                         Err(RuntimeControlFlow::Break(_)) => runtime_panic!("Illegal break outside block or loop"),
-                        x @ Err(RuntimeControlFlow::Panic(_)) => x,
+                        Err(x @ RuntimeControlFlow::Panic(_)) => {
+                            return Err(x.called_from(FrameKind::Call(fun_name.clone()), self.span, scope));
+                        }
+                    };
+                    match &decl.return_ty {
+                        Some(ty) => match convert_to_type(value, ty)? {
+                            Ok(value) => Ok(value),
+                            Err(value) => runtime_panic!(
+                                "function {:?} returned {}, but its return type is {}",
+                                fun_name,
+                                value,
+                                ty.id
+                            ),
+                        },
+                        None => Ok(value),
                     }
                 } else if decl.modifiers.contains(Modifiers::EXTERN) {
-                    execute_builtin_function(fun_name, evaluated_args)
+                    // natives take their arguments in order, with variadic arguments spread out
+                    let mut variables = new_scope.variables.take();
+                    let mut values = Vec::with_capacity(decl.args.len());
+                    for arg in &decl.args {
+                        match (arg.variadic, variables.remove(&arg.name)) {
+                            (true, Some(Value::Array(rest))) => values.extend(rest),
+                            (_, Some(value)) => values.push(value),
+                            (_, None) => {}
+                        }
+                    }
+                    let native = match scope.module_info() {
+                        Some(info) => info.cache()?.natives.borrow().get(fun_name).cloned(),
+                        None => None,
+                    };
+                    match native {
+                        Some(native) => native.call(fun_name, values),
+                        None => runtime_panic!("extern function {:?} isn't provided by the host", fun_name),
+                    }
                 } else {
                     runtime_panic!("function {:?} doesn't have a body", fun_name);
                 }
             }
-            Expr::Block { body } => {
-                let mut new_scope = Scope::new(scope);
-                match execute_stmts(body, &mut new_scope) {
+            ExprKind::Block { body } => {
+                let new_scope = Scope::new(scope);
+                match execute_stmts(body, &new_scope) {
                     Ok(x) | Err(RuntimeControlFlow::Break(x)) => Ok(x),
                     x => x,
                 }
             }
-            Expr::If {
+            ExprKind::If {
                 condition,
                 body,
                 r#else,
@@ -312,31 +560,48 @@ impl Expr {
                     Value::Bool(x) => x,
                     _ => runtime_panic!("expected bool type for condition"),
                 } {
-                    let mut new_scope = Scope::new(scope);
-                    execute_stmts(body, &mut new_scope)
+                    let new_scope = Scope::new(scope);
+                    execute_stmts(body, &new_scope)
                 } else if let Some(r#else) = r#else {
-                    let mut new_scope = Scope::new(scope);
-                    r#else.execute(&mut new_scope)
+                    let new_scope = Scope::new(scope);
+                    r#else.execute(&new_scope)
                 } else {
                     Ok(Value::unit())
                 }
             }
-            Expr::Loop { body } => loop {
-                let mut new_scope = Scope::new(scope);
-                match execute_stmts(body, &mut new_scope) {
+            ExprKind::Loop { body } => loop {
+                let new_scope = Scope::new(scope);
+                match execute_stmts(body, &new_scope) {
                     Err(RuntimeControlFlow::Break(x)) => break Ok(x),
                     Err(RuntimeControlFlow::Continue) => {}
                     Err(x) => break Err(x),
                     _ => {}
                 }
             },
-            Expr::While { condition, body } => {
+            ExprKind::While { condition, body } => {
                 while match condition.execute(scope)? {
                     Value::Bool(x) => x,
                     _ => runtime_panic!("expected bool type for condition"),
                 } {
-                    let mut new_scope = Scope::new(scope);
-                    match execute_stmts(body, &mut new_scope) {
+                    let new_scope = Scope::new(scope);
+                    match execute_stmts(body, &new_scope) {
+                        Err(RuntimeControlFlow::Break(x)) => return Ok(x),
+                        Err(RuntimeControlFlow::Continue) => {}
+                        Err(x) => return Err(x),
+                        _ => {}
+                    }
+                }
+                Ok(Value::unit())
+            }
+            ExprKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                for item in iterable.execute(scope)?.try_iter()? {
+                    let new_scope = Scope::new(scope);
+                    new_scope.define(binding.clone(), item);
+                    match execute_stmts(body, &new_scope) {
                         Err(RuntimeControlFlow::Break(x)) => return Ok(x),
                         Err(RuntimeControlFlow::Continue) => {}
                         Err(x) => return Err(x),
@@ -345,35 +610,137 @@ impl Expr {
                 }
                 Ok(Value::unit())
             }
-            _ => todo!(),
+            ExprKind::Match { value, arms } => {
+                let value = value.execute(scope)?;
+                for arm in arms {
+                    let mut bindings = Vec::new();
+                    if !arm.pattern.matches(&value, scope, &mut bindings)? {
+                        continue;
+                    }
+                    let new_scope = Scope::new(scope);
+                    for (name, value) in bindings {
+                        new_scope.define(name, value);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        match guard.execute(&new_scope)? {
+                            Value::Bool(true) => {}
+                            Value::Bool(false) => continue,
+                            _ => runtime_panic!("expected bool type for match guard"),
+                        }
+                    }
+                    return arm.body.execute(&new_scope);
+                }
+                runtime_panic!("no match arm matched {}", value)
+            }
+            ExprKind::Try { body, binding, handler } => match execute_stmts(body, &Scope::new(scope)) {
+                Err(RuntimeControlFlow::Panic(panic)) => {
+                    let new_scope = Scope::new(scope);
+                    new_scope.define(binding.clone(), panic.value);
+                    execute_stmts(handler, &new_scope)
+                }
+                x => x,
+            },
+            ExprKind::Range { start, end, inclusive } => {
+                let start = start.execute(scope)?;
+                let end = end.execute(scope)?;
+                // both bounds are given the same type, so that every element
+                // of the range fits in it
+                let ty = match (NumType::of(&start), NumType::of(&end)) {
+                    (Some(x), Some(y)) if !x.is_float() && !y.is_float() => x.promote(y),
+                    _ => runtime_panic!("range bounds must be integers"),
+                };
+                Ok(Value::Range(Box::new(Range {
+                    start: cast(start, ty)?,
+                    end: cast(end, ty)?,
+                    inclusive: *inclusive,
+                })))
+            }
+            ExprKind::Error => runtime_panic!("Cannot execute AST with errors!"),
+        }
+    }
+}
+
+/// An evaluated [`PathSegment`].
+enum PathKey<'a> {
+    Property(&'a Ident),
+    Index(Value),
+}
+
+impl<'a> PathKey<'a> {
+    fn get(&self, value: &Value) -> Result<Value, RuntimeControlFlow> {
+        match self {
+            PathKey::Property(ident) => value.try_get_property(ident),
+            PathKey::Index(index) => value.try_get_index(index),
+        }
+    }
+}
+
+/// Replaces the value at `keys[depth..]` within `target`, creating the last property
+/// if it doesn't exist yet. `root` and the full `keys` are only used for errors.
+fn assign_path(
+    root: &Ident,
+    keys: &[PathKey],
+    depth: usize,
+    target: &mut Value,
+    value: Value,
+) -> Result<(), RuntimeControlFlow> {
+    let Some(key) = keys.get(depth) else {
+        *target = value;
+        return Ok(());
+    };
+    match (target, key) {
+        (Value::Object(object), PathKey::Property(Ident { id: name }) | PathKey::Index(Value::Str(name))) => {
+            if depth + 1 == keys.len() {
+                object.borrow_mut().values.insert(name.clone(), value);
+                return Ok(());
+            }
+            // don't hold onto the borrow while recursing, since
+            // the rest of the path might lead back to this object
+            let mut property = match object.borrow().values.get(name) {
+                Some(x) => x.clone(),
+                None => runtime_panic!(
+                    "can't assign to {}: {} doesn't exist",
+                    display_path(root, keys),
+                    display_path(root, &keys[..=depth]),
+                ),
+            };
+            assign_path(root, keys, depth + 1, &mut property, value)?;
+            object.borrow_mut().values.insert(name.clone(), property);
+        }
+        (Value::Array(items) | Value::Tuple(items), PathKey::Index(index)) => {
+            let index = checked_index(index, items.len())?;
+            assign_path(root, keys, depth + 1, &mut items[index], value)?;
         }
+        (Value::Object(_), PathKey::Index(_)) => runtime_panic!("object properties must be indexed by string"),
+        (Value::Module(module), _) => runtime_panic!(
+            "can't assign to {}: items of module {} can only be assigned from within it",
+            display_path(root, keys),
+            module.path.display(),
+        ),
+        (_, PathKey::Property(_)) => runtime_panic!("only objects can have properties"),
+        (_, PathKey::Index(_)) => runtime_panic!("only arrays, tuples and objects can be indexed"),
     }
+    Ok(())
+}
+
+fn display_path(root: &Ident, keys: &[PathKey]) -> String {
+    let mut out = root.id.clone();
+    for key in keys {
+        match key {
+            PathKey::Property(ident) => {
+                out.push('.');
+                out.push_str(&ident.id);
+            }
+            PathKey::Index(Value::Str(x)) => out.push_str(&format!("[{:?}]", x)),
+            PathKey::Index(x) => out.push_str(&format!("[{}]", x)),
+        }
+    }
+    out
 }
 
 fn execute_builtin_binop(op: BinaryOp, left: Value, right: Value) -> Result<Value, RuntimeControlFlow> {
-    if let Value::U64(left) = left && let Value::U64(right) = right {
-        Ok(match op {
-            BinaryOp::Add => Value::U64(left + right),
-            BinaryOp::Sub => Value::U64(left - right),
-            BinaryOp::Mul => Value::U64(left * right),
-            BinaryOp::Div => Value::U64(left / right),
-            BinaryOp::Pow => Value::U64(left.pow(right as u32)),
-            BinaryOp::Rem => Value::U64(left % right),
-            BinaryOp::BitAnd => Value::U64(left & right),
-            BinaryOp::BitOr => Value::U64(left | right),
-            BinaryOp::Xor => Value::U64(left ^ right),
-            BinaryOp::Shl => Value::U64(left << right),
-            BinaryOp::Shr => Value::U64(left >> right),
-            BinaryOp::Rol => Value::U64(left.rotate_left(right as u32)),
-            BinaryOp::Ror => Value::U64(left.rotate_right(right as u32)),
-            BinaryOp::Eq => Value::Bool(left == right),
-            BinaryOp::Neq => Value::Bool(left != right),
-            BinaryOp::Lt => Value::Bool(left < right),
-            BinaryOp::Lte => Value::Bool(left <= right),
-            BinaryOp::Gt => Value::Bool(left > right),
-            BinaryOp::Gte => Value::Bool(left >= right),
-            _ => runtime_panic!("u64s can't do that"),
-        })
+    if NumType::of(&left).is_some() && NumType::of(&right).is_some() {
+        execute_numeric_binop(op, left, right)
     } else if let Value::Bool(left) = left && let Value::Bool(right) = right {
         Ok(match op {
             BinaryOp::And => Value::Bool(left && right),
@@ -382,45 +749,104 @@ fn execute_builtin_binop(op: BinaryOp, left: Value, right: Value) -> Result<Valu
             BinaryOp::Neq => Value::Bool(left != right),
             _ => runtime_panic!("bools can't do that"),
         })
+    } else if matches!(left, Value::Str(_) | Value::Char(_)) || matches!(right, Value::Str(_) | Value::Char(_)) {
+        execute_string_binop(op, left, right)
     } else {
-        todo!()
+        runtime_panic!("can't apply {:?} to {} and {}", op, left, right)
     }
 }
 
 fn execute_builtin_unary_op(op: UnaryOp, input: Value) -> Result<Value, RuntimeControlFlow> {
-    if let Value::U64(input) = input {
-        Ok(match op {
-            UnaryOp::Plus => Value::U64(input),
-            UnaryOp::Minus => Value::I64(-(input as i64)),
-            UnaryOp::BitNot => Value::U64(!input),
-            _ => runtime_panic!("u64s can't do that"),
-        })
+    if NumType::of(&input).is_some() {
+        execute_numeric_unary_op(op, input)
     } else if let Value::Bool(input) = input {
         Ok(match op {
             UnaryOp::Not => Value::Bool(!input),
             _ => runtime_panic!("bools can't do that"),
         })
     } else {
-        todo!()
+        runtime_panic!("can't apply {:?} to {}", op, input)
     }
 }
 
-fn execute_builtin_function(name: &Ident, args: HashMap<Ident, Value>) -> Result<Value, RuntimeControlFlow> {
-    match name.id.as_str() {
-        "panic" => Err(RuntimeControlFlow::Panic(
-            args.into_iter()
-                .next()
-                .map(|x| x.1)
-                .unwrap_or(Value::Str("explicit panic".to_string())),
-        )),
-        "print" => {
-            print!("{}", args.iter().next().unwrap().1);
-            Ok(Value::unit())
-        }
-        "println" => {
-            println!("{}", args.iter().next().unwrap().1);
-            Ok(Value::unit())
+/// Binds the arguments of a call to the parameters they're passed to in
+/// `scope`. Positional arguments that don't fit in the other parameters are
+/// collected into an array for the variadic parameter, and parameters without
+/// an argument get their default value, which can refer to earlier parameters.
+fn bind_args(decl: &FnDecl, args: Vec<(Option<&Ident>, Value)>, scope: &Rc<Scope>) -> Result<(), RuntimeControlFlow> {
+    let mut bound = vec![None; decl.args.len()];
+    let variadic = decl.args.iter().position(|x| x.variadic);
+    let mut rest = Vec::new();
+    let mut next_positional = 0;
+    for (name, value) in args {
+        let index = match name {
+            Some(name) => match decl.args.iter().position(|x| &x.name == name) {
+                Some(index) if Some(index) != variadic => index,
+                Some(_) => runtime_panic!("variadic argument {:?} can't be passed by name", name),
+                None => runtime_panic!("function {:?} has no argument named {:?}", decl.name, name),
+            },
+            None if Some(next_positional) == variadic => {
+                rest.push(value);
+                continue;
+            }
+            None if next_positional < decl.args.len() => {
+                next_positional += 1;
+                next_positional - 1
+            }
+            None => runtime_panic!(
+                "function {:?} takes at most {} arguments, but more were given",
+                decl.name,
+                decl.args.len()
+            ),
+        };
+        if bound[index].is_some() {
+            runtime_panic!("argument {:?} was given more than once", decl.args[index].name);
         }
-        _ => runtime_panic!("unknown builtin function {:?}", name),
+        bound[index] = Some(value);
+    }
+
+    for (arg, value) in decl.args.iter().zip(bound) {
+        let value = match value {
+            Some(value) => check_arg_type(decl, arg, value)?,
+            None if arg.variadic => {
+                let mut values = Vec::with_capacity(rest.len());
+                for value in std::mem::take(&mut rest) {
+                    values.push(check_arg_type(decl, arg, value)?);
+                }
+                Value::Array(values)
+            }
+            None => match &arg.default {
+                Some(default) => check_arg_type(decl, arg, default.execute(scope)?)?,
+                None => runtime_panic!("missing argument {:?} to function {:?}", arg.name, decl.name),
+            },
+        };
+        scope.define(arg.name.clone(), value);
+    }
+    Ok(())
+}
+
+fn check_arg_type(decl: &FnDecl, arg: &FnArg, value: Value) -> Result<Value, RuntimeControlFlow> {
+    match convert_to_type(value, &arg.ty)? {
+        Ok(value) => Ok(value),
+        Err(value) => runtime_panic!(
+            "expected {} for argument {:?} of function {:?}, found {}",
+            arg.ty.id,
+            arg.name,
+            decl.name,
+            value
+        ),
+    }
+}
+
+/// Converts a value to the type with the given name, or gives it back if it
+/// isn't of that type. Numbers are converted between types, panicking if they
+/// don't fit, but floats are never converted to integers.
+fn convert_to_type(value: Value, ty: &Ident) -> Result<Result<Value, Value>, RuntimeControlFlow> {
+    // unknown types are reported by the type checker
+    let ty = Type::from_name(ty).unwrap_or(Type::Any);
+    match ty {
+        _ if !ty.accepts(Type::of(&value)) => Ok(Err(value)),
+        Type::Num(ty) => cast(value, ty).map(Ok),
+        _ => Ok(Ok(value)),
     }
 }
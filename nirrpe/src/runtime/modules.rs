@@ -0,0 +1,210 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+
+use crate::parse::ast::{Decl, Modifiers, Program, StmtKind};
+use crate::parse::ident::Ident;
+use crate::runtime::gc::Heap;
+use crate::runtime::loader::ModuleLoader;
+use crate::runtime::natives::Natives;
+use crate::runtime::value::Value;
+use crate::runtime::{execute_stmts, runtime_panic, RuntimeControlFlow, Scope};
+use crate::Engine;
+
+/// A loaded file, with its own global scope. Only the items declared `pub`
+/// in it can be accessed from other modules.
+pub struct Module {
+    pub path: PathBuf,
+    pub scope: Rc<Scope>,
+    pub exports: HashSet<Ident>,
+}
+
+impl Module {
+    /// Gets a `pub` item of this module.
+    pub fn get(&self, name: &Ident) -> Result<Value, RuntimeControlFlow> {
+        match self.scope.get_value(name) {
+            Some(x) if self.exports.contains(name) => Ok(x),
+            Some(_) => runtime_panic!("{:?} is private to module {}", name, self.path.display()),
+            None => runtime_panic!("module {} has no item named {:?}", self.path.display(), name),
+        }
+    }
+}
+
+impl Debug for Module {
+    // the scope usually contains functions that refer back to it
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Module")
+            .field("path", &self.path)
+            .field("exports", &self.exports)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where the global scope of a module was loaded from.
+pub struct ModuleInfo {
+    pub path: PathBuf,
+    /// The text the module was compiled from.
+    pub source: Rc<str>,
+    /// Finds the modules this one imports.
+    pub loader: Rc<dyn ModuleLoader>,
+    /// Weak, since the cache owns every module it loaded.
+    pub cache: Weak<ModuleCache>,
+}
+
+impl ModuleInfo {
+    /// Gets the cache of the runtime this module was loaded by.
+    pub fn cache(&self) -> Result<Rc<ModuleCache>, RuntimeControlFlow> {
+        match self.cache.upgrade() {
+            Some(x) => Ok(x),
+            None => runtime_panic!("module {} outlived its runtime", self.path.display()),
+        }
+    }
+}
+
+/// Every module loaded by a runtime, so that a file imported from several
+/// places is only executed once.
+pub struct ModuleCache {
+    /// The scope every module's global scope is nested in.
+    pub globals: Rc<Scope>,
+    /// The functions `extern fn`s are resolved to.
+    pub natives: RefCell<Natives>,
+    modules: RefCell<HashMap<PathBuf, Rc<Module>>>,
+    /// The modules being executed, innermost last.
+    loading: RefCell<Vec<PathBuf>>,
+    /// The number of Nirrpe function calls being executed.
+    depth: Cell<usize>,
+    /// How deeply function calls can be nested before panicking.
+    pub max_depth: Cell<usize>,
+    /// Every function, object and module created by this runtime.
+    pub heap: Heap,
+}
+
+/// How deeply Nirrpe function calls can be nested by default, so that runaway
+/// recursion panics instead of using up the host's memory. The stack grows
+/// as needed, so this isn't limited by the size of the host's stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// How deeply imports can be nested before panicking.
+pub const MAX_IMPORT_DEPTH: usize = 64;
+
+impl ModuleCache {
+    pub fn new(globals: Rc<Scope>) -> Self {
+        Self {
+            globals,
+            natives: RefCell::new(Natives::builtins()),
+            modules: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+            depth: Cell::new(0),
+            max_depth: Cell::new(DEFAULT_MAX_CALL_DEPTH),
+            heap: Heap::new(),
+        }
+    }
+
+    /// Counts a function call until the returned guard is dropped,
+    /// panicking if calls are nested too deeply.
+    pub fn enter_call(self: &Rc<Self>) -> Result<CallGuard, RuntimeControlFlow> {
+        if self.depth.get() >= self.max_depth.get() {
+            runtime_panic!("stack overflow");
+        }
+        self.depth.set(self.depth.get() + 1);
+        if self.heap.should_collect() {
+            self.heap.collect();
+        }
+        Ok(CallGuard(self.clone()))
+    }
+
+    /// Loads the module at `path` with the loader of the module that imports it.
+    pub fn import(self: &Rc<Self>, importer: &ModuleInfo, path: &str) -> Result<Rc<Module>, RuntimeControlFlow> {
+        let path = match importer.loader.resolve(&importer.path, path) {
+            Ok(x) => x,
+            Err(err) => runtime_panic!("{}", err),
+        };
+        if let Some(module) = self.modules.borrow().get(&path) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.loading.borrow().iter().position(|x| x == &path) {
+            let cycle = self.loading.borrow()[start..]
+                .iter()
+                .chain([&path])
+                .map(|x| x.display().to_string())
+                .collect::<Vec<_>>();
+            runtime_panic!("import cycle detected: {}", cycle.join(" -> "));
+        }
+        if self.loading.borrow().len() > MAX_IMPORT_DEPTH {
+            runtime_panic!("imports nested more than {} deep", MAX_IMPORT_DEPTH);
+        }
+        let src = match importer.loader.load(&path) {
+            Ok(x) => x,
+            Err(err) => runtime_panic!("{}", err),
+        };
+        let script = match Engine::new().compile(&src, &path.display().to_string()) {
+            Ok(x) => x,
+            Err(diagnostics) => runtime_panic!("module {} has errors:\n{}", path.display(), diagnostics),
+        };
+        let scope = self.module_scope(path, src.into(), importer.loader.clone());
+        self.execute(scope, &script.program).map(|(module, _)| module)
+    }
+
+    /// Creates the global scope of the module at `path`, compiled from `source`.
+    pub fn module_scope(self: &Rc<Self>, path: PathBuf, source: Rc<str>, loader: Rc<dyn ModuleLoader>) -> Rc<Scope> {
+        let info = ModuleInfo {
+            path,
+            source,
+            loader,
+            cache: Rc::downgrade(self),
+        };
+        Scope::module(info, &self.globals)
+    }
+
+    /// Executes a program in the global scope of a module, returning the
+    /// module along with the value of its last statement.
+    pub fn execute(
+        self: &Rc<Self>,
+        scope: Rc<Scope>,
+        program: &Program,
+    ) -> Result<(Rc<Module>, Value), RuntimeControlFlow> {
+        let path = match scope.module_info() {
+            Some(info) => info.path.clone(),
+            None => runtime_panic!("modules must be executed in a module scope"),
+        };
+        self.natives.borrow().check_externs(program, &scope)?;
+        self.loading.borrow_mut().push(path.clone());
+        let result = execute_stmts(&program.stmts, &scope);
+        self.loading.borrow_mut().pop();
+        let value = match result {
+            Ok(x) => x,
+            Err(RuntimeControlFlow::Continue) => runtime_panic!("Illegal top-level continue"),
+            Err(RuntimeControlFlow::Break(_)) => runtime_panic!("Illegal top-level break"),
+            Err(RuntimeControlFlow::Return(_)) => runtime_panic!("Illegal top-level return"),
+            Err(x @ RuntimeControlFlow::Panic(_)) => return Err(x),
+        };
+
+        let mut exports = HashSet::new();
+        for stmt in &program.stmts {
+            match &stmt.kind {
+                StmtKind::Decl(Decl::FnDecl(decl)) if decl.modifiers.contains(Modifiers::PUB) => {
+                    exports.insert(decl.name.clone());
+                }
+                StmtKind::Decl(Decl::LetDecl(r#let)) if r#let.modifiers.contains(Modifiers::PUB) => {
+                    exports.extend(r#let.pattern.names().into_iter().cloned());
+                }
+                _ => {}
+            }
+        }
+        let module = Rc::new(Module { path, scope, exports });
+        self.heap.track(&Value::Module(module.clone()));
+        self.modules.borrow_mut().insert(module.path.clone(), module.clone());
+        Ok((module, value))
+    }
+}
+
+/// A function call being executed, see [`ModuleCache::enter_call`].
+pub struct CallGuard(Rc<ModuleCache>);
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
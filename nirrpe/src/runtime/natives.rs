@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::parse::ast::{Decl, FnDecl, Modifiers, Program, StmtKind};
+use crate::parse::ident::Ident;
+use crate::runtime::trace::Panic;
+use crate::runtime::value::Value;
+use crate::runtime::{runtime_panic, RuntimeControlFlow, Scope};
+
+/// A function provided by the host, which scripts call through an
+/// `extern fn` declaration with the same name.
+#[derive(Clone)]
+pub struct NativeFunction {
+    /// The number of arguments this function takes, or `None` if it takes
+    /// any number of them.
+    pub arity: Option<usize>,
+    fun: Rc<dyn Fn(Vec<Value>) -> Result<Value, Panic>>,
+}
+
+impl NativeFunction {
+    /// Creates a function that takes any number of arguments.
+    pub fn variadic(fun: impl Fn(Vec<Value>) -> Result<Value, Panic> + 'static) -> Self {
+        Self {
+            arity: None,
+            fun: Rc::new(fun),
+        }
+    }
+
+    pub fn call(&self, name: &Ident, args: Vec<Value>) -> Result<Value, RuntimeControlFlow> {
+        if let Some(arity) = self.arity && arity != args.len() {
+            runtime_panic!(
+                "extern function {:?} takes {} arguments, but {} were given",
+                name,
+                arity,
+                args.len()
+            );
+        }
+        Ok((self.fun)(args)?)
+    }
+}
+
+/// The native functions registered on a runtime.
+#[derive(Clone, Default)]
+pub struct Natives {
+    functions: HashMap<Ident, NativeFunction>,
+}
+
+impl Natives {
+    /// Creates a registry with the functions every script can use.
+    pub fn builtins() -> Self {
+        let mut natives = Self::default();
+        natives.register_variadic("panic", |args| {
+            Err(Panic::new(
+                args.into_iter()
+                    .next()
+                    .unwrap_or(Value::Str("explicit panic".to_string())),
+            ))
+        });
+        natives.register_variadic("print", |args| {
+            print!("{}", join_args(&args));
+            Ok(Value::unit())
+        });
+        natives.register_variadic("println", |args| {
+            println!("{}", join_args(&args));
+            Ok(Value::unit())
+        });
+        natives
+    }
+
+    /// Registers a Rust function, converting its arguments from and its
+    /// result to [`Value`]s.
+    pub fn register<Args>(&mut self, name: &str, fun: impl IntoNativeFunction<Args>) {
+        self.functions.insert(Ident::new(name), fun.into_native());
+    }
+
+    /// Registers a Rust function that gets the arguments it was called with as is.
+    pub fn register_variadic(&mut self, name: &str, fun: impl Fn(Vec<Value>) -> Result<Value, Panic> + 'static) {
+        self.functions.insert(Ident::new(name), NativeFunction::variadic(fun));
+    }
+
+    pub fn get(&self, name: &Ident) -> Option<&NativeFunction> {
+        self.functions.get(name)
+    }
+
+    /// Checks that every `extern fn` declared without a body has a native
+    /// function that can be called with its arguments. The type checker only
+    /// allows these at the top level of a module, so nothing else is walked.
+    pub fn check_externs(&self, program: &Program, scope: &Scope) -> Result<(), RuntimeControlFlow> {
+        for stmt in &program.stmts {
+            let StmtKind::Decl(Decl::FnDecl(decl)) = &stmt.kind else {
+                continue;
+            };
+            if decl.body.is_some() || !decl.modifiers.contains(Modifiers::EXTERN) {
+                continue;
+            }
+            self.check_extern(decl).map_err(|x| x.located_at(decl.span, scope))?;
+        }
+        Ok(())
+    }
+
+    fn check_extern(&self, decl: &FnDecl) -> Result<(), RuntimeControlFlow> {
+        let Some(native) = self.get(&decl.name) else {
+            runtime_panic!("extern function {:?} isn't provided by the host", decl.name);
+        };
+        match native.arity {
+            Some(arity) if decl.args.iter().any(|x| x.variadic) => runtime_panic!(
+                "extern function {:?} takes {} arguments, but is declared as variadic",
+                decl.name,
+                arity
+            ),
+            Some(arity) if arity != decl.args.len() => runtime_panic!(
+                "extern function {:?} takes {} arguments, but is declared with {}",
+                decl.name,
+                arity,
+                decl.args.len()
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn join_args(args: &[Value]) -> String {
+    args.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// Converts a [`Value`] passed to a native function into a Rust value.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, Panic>;
+}
+
+/// Converts a Rust value returned from a native function into a [`Value`].
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// The result of a native function, which may fail with a panic.
+pub trait IntoNativeResult {
+    fn into_native_result(self) -> Result<Value, Panic>;
+}
+
+/// A Rust function that can be registered as a [`NativeFunction`], with
+/// `Args` being a tuple of its argument types.
+pub trait IntoNativeFunction<Args> {
+    fn into_native(self) -> NativeFunction;
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, Panic> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, Panic> {
+        match value {
+            Value::Bool(x) => Ok(x),
+            x => runtime_panic!("expected a bool, found {}", x),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self, Panic> {
+        match value {
+            Value::Char(x) => Ok(x),
+            x => runtime_panic!("expected a char, found {}", x),
+        }
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Value {
+        Value::Char(self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, Panic> {
+        match value {
+            Value::Str(x) => Ok(x),
+            Value::Char(x) => Ok(x.to_string()),
+            x => runtime_panic!("expected a str, found {}", x),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::Str(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::Str(self.to_string())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::unit()
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, Panic> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_value).collect(),
+            x => runtime_panic!("expected an array, found {}", x),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+/// Integers can be converted from any integer value that fits in them.
+macro int_conversions($($ty:ident => $variant:ident),+$(,)?) {
+    $(
+        impl FromValue for $ty {
+            fn from_value(value: Value) -> Result<Self, Panic> {
+                match value.as_i128().map($ty::try_from) {
+                    Some(Ok(x)) => Ok(x),
+                    Some(Err(_)) => runtime_panic!("{} doesn't fit in a {}", value, stringify!($ty)),
+                    None => runtime_panic!("expected an integer, found {}", value),
+                }
+            }
+        }
+
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+        }
+    )+
+}
+
+int_conversions!(
+    i8 => I8,
+    u8 => U8,
+    i16 => I16,
+    u16 => U16,
+    i32 => I32,
+    u32 => U32,
+    i64 => I64,
+    u64 => U64,
+);
+
+/// Floats can be converted from any numeric value, possibly losing precision.
+macro float_conversions($($ty:ident => $variant:ident),+$(,)?) {
+    $(
+        impl FromValue for $ty {
+            fn from_value(value: Value) -> Result<Self, Panic> {
+                match value.as_f64() {
+                    Some(x) => Ok(x as $ty),
+                    None => runtime_panic!("expected a number, found {}", value),
+                }
+            }
+        }
+
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+        }
+    )+
+}
+
+float_conversions!(f32 => F32, f64 => F64);
+
+impl<T: IntoValue> IntoNativeResult for T {
+    fn into_native_result(self) -> Result<Value, Panic> {
+        Ok(self.into_value())
+    }
+}
+
+/// Native functions that return an `Err` panic with the error's message.
+impl<T: IntoValue, E: Display> IntoNativeResult for Result<T, E> {
+    fn into_native_result(self) -> Result<Value, Panic> {
+        match self {
+            Ok(x) => Ok(x.into_value()),
+            Err(err) => runtime_panic!("{}", err),
+        }
+    }
+}
+
+macro native_function($($arg:ident),*) {
+    impl<F, R, $($arg),*> IntoNativeFunction<($($arg,)*)> for F
+    where
+        F: Fn($($arg),*) -> R + 'static,
+        R: IntoNativeResult,
+        $($arg: FromValue),*
+    {
+        #[allow(non_snake_case, unused_mut, unused_variables)]
+        fn into_native(self) -> NativeFunction {
+            NativeFunction {
+                arity: Some(<[&str]>::len(&[$(stringify!($arg)),*])),
+                fun: Rc::new(move |args: Vec<Value>| {
+                    // the number of arguments was already checked by `NativeFunction::call`
+                    let mut args = args.into_iter();
+                    $(let $arg = $arg::from_value(args.next().unwrap_or_else(Value::unit))?;)*
+                    self($($arg),*).into_native_result()
+                }),
+            }
+        }
+    }
+}
+
+native_function!();
+native_function!(A);
+native_function!(A, B);
+native_function!(A, B, C);
+native_function!(A, B, C, D);
+native_function!(A, B, C, D, E);
+native_function!(A, B, C, D, E, G);
@@ -0,0 +1,214 @@
+use std::cmp::Ordering;
+
+use crate::parse::ast::{BinaryOp, NumType, UnaryOp};
+use crate::runtime::value::Value;
+use crate::runtime::{runtime_panic, RuntimeControlFlow};
+
+impl NumType {
+    pub fn of(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::I8(_) => Self::I8,
+            Value::U8(_) => Self::U8,
+            Value::I16(_) => Self::I16,
+            Value::U16(_) => Self::U16,
+            Value::I32(_) => Self::I32,
+            Value::U32(_) => Self::U32,
+            Value::I64(_) => Self::I64,
+            Value::U64(_) => Self::U64,
+            Value::F32(_) => Self::F32,
+            Value::F64(_) => Self::F64,
+            _ => return None,
+        })
+    }
+
+    /// Finds the type that the operands of a binary operation are converted to:
+    ///
+    /// - if either operand is a float, the widest float type involved
+    /// - if both operands are integers of the same signedness, the wider type
+    /// - otherwise, the narrowest signed type that can hold the unsigned operand
+    ///   (capped at `i64`) or the signed operand, whichever is wider
+    pub fn promote(self, other: Self) -> Self {
+        match (self.is_float(), other.is_float()) {
+            (true, true) => {
+                if self.bits() >= other.bits() {
+                    self
+                } else {
+                    other
+                }
+            }
+            (true, false) => self,
+            (false, true) => other,
+            (false, false) if self.is_signed() == other.is_signed() => {
+                Self::int(self.bits().max(other.bits()), self.is_signed())
+            }
+            (false, false) => {
+                let (signed, unsigned) = if self.is_signed() { (self, other) } else { (other, self) };
+                Self::int(signed.bits().max((unsigned.bits() * 2).min(64)), true)
+            }
+        }
+    }
+
+    /// Creates a value of this integer type, or `None` if `x` doesn't fit in it.
+    pub fn int_value(self, x: i128) -> Option<Value> {
+        match self {
+            Self::I8 => x.try_into().ok().map(Value::I8),
+            Self::U8 => x.try_into().ok().map(Value::U8),
+            Self::I16 => x.try_into().ok().map(Value::I16),
+            Self::U16 => x.try_into().ok().map(Value::U16),
+            Self::I32 => x.try_into().ok().map(Value::I32),
+            Self::U32 => x.try_into().ok().map(Value::U32),
+            Self::I64 => x.try_into().ok().map(Value::I64),
+            Self::U64 => x.try_into().ok().map(Value::U64),
+            Self::F32 | Self::F64 => None,
+        }
+    }
+}
+
+/// Converts a numeric value to the given type. Floats are never implicitly
+/// converted to integers, and integers that don't fit in `ty` cause a panic.
+pub fn cast(value: Value, ty: NumType) -> Result<Value, RuntimeControlFlow> {
+    if NumType::of(&value) == Some(ty) {
+        Ok(value)
+    } else if ty.is_float() {
+        let Some(x) = value.as_f64() else {
+            runtime_panic!("can't convert {} to {}", value, ty.name());
+        };
+        Ok(match ty {
+            NumType::F32 => Value::F32(x as f32),
+            _ => Value::F64(x),
+        })
+    } else {
+        let Some(x) = value.as_i128() else {
+            runtime_panic!("can't implicitly convert {} to {}", value, ty.name());
+        };
+        match ty.int_value(x) {
+            Some(x) => Ok(x),
+            None => runtime_panic!("{} doesn't fit in {}", x, ty.name()),
+        }
+    }
+}
+
+/// Applies a binary operator to two numeric values.
+pub fn execute_numeric_binop(op: BinaryOp, left: Value, right: Value) -> Result<Value, RuntimeControlFlow> {
+    if matches!(
+        op,
+        BinaryOp::Eq | BinaryOp::Neq | BinaryOp::Lt | BinaryOp::Lte | BinaryOp::Gt | BinaryOp::Gte
+    ) {
+        // comparisons are done exactly without promoting, so that they never fail
+        let ordering = match (left.as_i128(), right.as_i128()) {
+            (Some(left), Some(right)) => Some(left.cmp(&right)),
+            _ => left.as_f64().partial_cmp(&right.as_f64()),
+        };
+        return Ok(Value::Bool(match op {
+            BinaryOp::Eq => ordering == Some(Ordering::Equal),
+            BinaryOp::Neq => ordering != Some(Ordering::Equal),
+            BinaryOp::Lt => ordering == Some(Ordering::Less),
+            BinaryOp::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            BinaryOp::Gt => ordering == Some(Ordering::Greater),
+            BinaryOp::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            _ => unreachable!(),
+        }));
+    }
+
+    let (Some(left_ty), Some(right_ty)) = (NumType::of(&left), NumType::of(&right)) else {
+        runtime_panic!("expected numbers, found {} and {}", left, right);
+    };
+    let ty = left_ty.promote(right_ty);
+    match (cast(left, ty)?, cast(right, ty)?) {
+        (Value::I8(left), Value::I8(right)) => int_binop!(op, left, right, I8),
+        (Value::U8(left), Value::U8(right)) => int_binop!(op, left, right, U8),
+        (Value::I16(left), Value::I16(right)) => int_binop!(op, left, right, I16),
+        (Value::U16(left), Value::U16(right)) => int_binop!(op, left, right, U16),
+        (Value::I32(left), Value::I32(right)) => int_binop!(op, left, right, I32),
+        (Value::U32(left), Value::U32(right)) => int_binop!(op, left, right, U32),
+        (Value::I64(left), Value::I64(right)) => int_binop!(op, left, right, I64),
+        (Value::U64(left), Value::U64(right)) => int_binop!(op, left, right, U64),
+        (Value::F32(left), Value::F32(right)) => float_binop!(op, left, right, F32),
+        (Value::F64(left), Value::F64(right)) => float_binop!(op, left, right, F64),
+        _ => unreachable!("both operands were cast to {:?}", ty),
+    }
+}
+
+/// Applies a unary operator to a numeric value.
+pub fn execute_numeric_unary_op(op: UnaryOp, input: Value) -> Result<Value, RuntimeControlFlow> {
+    let Some(ty) = NumType::of(&input) else {
+        runtime_panic!("expected a number, found {}", input);
+    };
+    Ok(match (op, input) {
+        (UnaryOp::Plus, x) => x,
+        (UnaryOp::Minus, Value::F32(x)) => Value::F32(-x),
+        (UnaryOp::Minus, Value::F64(x)) => Value::F64(-x),
+        (UnaryOp::Minus, x) => negate_int(x.as_i128().unwrap(), ty)?,
+        (UnaryOp::BitNot, Value::I8(x)) => Value::I8(!x),
+        (UnaryOp::BitNot, Value::U8(x)) => Value::U8(!x),
+        (UnaryOp::BitNot, Value::I16(x)) => Value::I16(!x),
+        (UnaryOp::BitNot, Value::U16(x)) => Value::U16(!x),
+        (UnaryOp::BitNot, Value::I32(x)) => Value::I32(!x),
+        (UnaryOp::BitNot, Value::U32(x)) => Value::U32(!x),
+        (UnaryOp::BitNot, Value::I64(x)) => Value::I64(!x),
+        (UnaryOp::BitNot, Value::U64(x)) => Value::U64(!x),
+        _ => runtime_panic!("{}s can't do that", ty.name()),
+    })
+}
+
+/// Negates an integer of type `ty`. Negating an unsigned integer gives the
+/// signed integer of the same size.
+fn negate_int(x: i128, ty: NumType) -> Result<Value, RuntimeControlFlow> {
+    match NumType::int(ty.bits(), true).int_value(-x) {
+        Some(x) => Ok(x),
+        None => runtime_panic!("attempt to negate with overflow"),
+    }
+}
+
+fn overflow_verb(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "add",
+        BinaryOp::Sub => "subtract",
+        BinaryOp::Mul => "multiply",
+        BinaryOp::Div => "divide",
+        BinaryOp::Pow => "raise to a power",
+        BinaryOp::Rem => "calculate the remainder",
+        BinaryOp::Shl => "shift left",
+        BinaryOp::Shr => "shift right",
+        _ => "compute",
+    }
+}
+
+macro int_binop($op:expr, $left:expr, $right:expr, $variant:ident) {{
+    let (op, left, right) = ($op, $left, $right);
+    let result = match op {
+        BinaryOp::Add => left.checked_add(right),
+        BinaryOp::Sub => left.checked_sub(right),
+        BinaryOp::Mul => left.checked_mul(right),
+        BinaryOp::Div if right == 0 => runtime_panic!("attempt to divide by zero"),
+        BinaryOp::Rem if right == 0 => runtime_panic!("attempt to calculate the remainder with a divisor of zero"),
+        BinaryOp::Div => left.checked_div(right),
+        BinaryOp::Rem => left.checked_rem(right),
+        BinaryOp::Pow => u32::try_from(right).ok().and_then(|right| left.checked_pow(right)),
+        BinaryOp::BitAnd => Some(left & right),
+        BinaryOp::BitOr => Some(left | right),
+        BinaryOp::Xor => Some(left ^ right),
+        BinaryOp::Shl => u32::try_from(right).ok().and_then(|right| left.checked_shl(right)),
+        BinaryOp::Shr => u32::try_from(right).ok().and_then(|right| left.checked_shr(right)),
+        BinaryOp::Rol => Some(left.rotate_left(right as u32)),
+        BinaryOp::Ror => Some(left.rotate_right(right as u32)),
+        _ => runtime_panic!("{}s can't do that", NumType::$variant.name()),
+    };
+    match result {
+        Some(x) => Ok(Value::$variant(x)),
+        None => runtime_panic!("attempt to {} with overflow", overflow_verb(op)),
+    }
+}}
+
+macro float_binop($op:expr, $left:expr, $right:expr, $variant:ident) {{
+    let (left, right) = ($left, $right);
+    Ok(Value::$variant(match $op {
+        BinaryOp::Add => left + right,
+        BinaryOp::Sub => left - right,
+        BinaryOp::Mul => left * right,
+        BinaryOp::Div => left / right,
+        BinaryOp::Pow => left.powf(right),
+        BinaryOp::Rem => left % right,
+        _ => runtime_panic!("{}s can't do that", NumType::$variant.name()),
+    }))
+}}
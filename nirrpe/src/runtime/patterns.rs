@@ -0,0 +1,151 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::parse::ast::Pattern;
+use crate::parse::ident::Ident;
+use crate::runtime::value::Value;
+use crate::runtime::{runtime_panic, RuntimeControlFlow, Scope};
+
+/// A description of why a value didn't match a pattern.
+type Mismatch = String;
+
+impl Pattern {
+    /// Checks whether a value matches this pattern, collecting the variables
+    /// it binds into `bindings`.
+    pub fn matches(
+        &self,
+        value: &Value,
+        scope: &Rc<Scope>,
+        bindings: &mut Vec<(Ident, Value)>,
+    ) -> Result<bool, RuntimeControlFlow> {
+        Ok(self.destructure(value, scope, bindings)?.is_ok())
+    }
+
+    /// Collects the variables this pattern binds into `bindings`, panicking
+    /// if the value doesn't match it.
+    pub fn bind(
+        &self,
+        value: &Value,
+        scope: &Rc<Scope>,
+        bindings: &mut Vec<(Ident, Value)>,
+    ) -> Result<(), RuntimeControlFlow> {
+        match self.destructure(value, scope, bindings)? {
+            Ok(()) => Ok(()),
+            Err(mismatch) => runtime_panic!("{}", mismatch),
+        }
+    }
+
+    /// Gets the names of the variables this pattern binds.
+    pub fn names(&self) -> Vec<&Ident> {
+        match self {
+            Pattern::Wildcard | Pattern::Lit(_) | Pattern::Range { .. } => Vec::new(),
+            Pattern::Binding(name) => vec![name],
+            Pattern::Object { props } => props.iter().flat_map(|(_, x)| x.names()).collect(),
+            Pattern::Array { items, rest } | Pattern::Tuple { items, rest } => {
+                items.iter().chain(rest.as_deref()).flat_map(Pattern::names).collect()
+            }
+        }
+    }
+
+    fn destructure(
+        &self,
+        value: &Value,
+        scope: &Rc<Scope>,
+        bindings: &mut Vec<(Ident, Value)>,
+    ) -> Result<Result<(), Mismatch>, RuntimeControlFlow> {
+        Ok(match self {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Binding(name) => {
+                bindings.push((name.clone(), value.clone()));
+                Ok(())
+            }
+            Pattern::Lit(lit) => {
+                let lit = lit.execute(scope)?;
+                match value.compare(&lit) {
+                    Some(Ordering::Equal) => Ok(()),
+                    _ => Err(format!("expected {}, found {}", lit, value)),
+                }
+            }
+            Pattern::Range { start, end, inclusive } => {
+                let range = (start.execute(scope)?, end.execute(scope)?);
+                let after_start = matches!(value.compare(&range.0), Some(Ordering::Greater | Ordering::Equal));
+                let before_end = match value.compare(&range.1) {
+                    Some(Ordering::Less) => true,
+                    Some(Ordering::Equal) => *inclusive,
+                    _ => false,
+                };
+                match after_start && before_end {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "expected a value in {}{}{}, found {}",
+                        range.0,
+                        if *inclusive { "..=" } else { ".." },
+                        range.1,
+                        value
+                    )),
+                }
+            }
+            Pattern::Object { props } => {
+                let Value::Object(object) = value else {
+                    return Ok(Err(format!("expected an object, found {}", value)));
+                };
+                for (name, pattern) in props {
+                    // clone the property so that the object isn't borrowed while destructuring
+                    let Some(prop) = object.borrow().values.get(&name.id).cloned() else {
+                        return Ok(Err(format!("property {:?} not found in object", name)));
+                    };
+                    if let Err(mismatch) = pattern.destructure(&prop, scope, bindings)? {
+                        return Ok(Err(mismatch));
+                    }
+                }
+                Ok(())
+            }
+            Pattern::Array { items, rest } => match value {
+                Value::Array(values) => destructure_items(items, rest, values, Value::Array, scope, bindings)?,
+                _ => Err(format!("expected an array, found {}", value)),
+            },
+            Pattern::Tuple { items, rest } => match value {
+                Value::Tuple(values) => destructure_items(items, rest, values, Value::Tuple, scope, bindings)?,
+                _ => Err(format!("expected a tuple, found {}", value)),
+            },
+        })
+    }
+}
+
+/// Destructures the elements of an array or tuple, passing any elements
+/// after `items` to `rest` as a new array or tuple.
+fn destructure_items(
+    items: &[Pattern],
+    rest: &Option<Box<Pattern>>,
+    values: &[Value],
+    collect_rest: fn(Vec<Value>) -> Value,
+    scope: &Rc<Scope>,
+    bindings: &mut Vec<(Ident, Value)>,
+) -> Result<Result<(), Mismatch>, RuntimeControlFlow> {
+    match rest {
+        None if values.len() != items.len() => {
+            return Ok(Err(format!(
+                "expected {} elements, found {}",
+                items.len(),
+                values.len()
+            )));
+        }
+        Some(_) if values.len() < items.len() => {
+            return Ok(Err(format!(
+                "expected at least {} elements, found {}",
+                items.len(),
+                values.len()
+            )));
+        }
+        _ => {}
+    }
+    for (pattern, value) in items.iter().zip(values) {
+        if let Err(mismatch) = pattern.destructure(value, scope, bindings)? {
+            return Ok(Err(mismatch));
+        }
+    }
+    match rest {
+        Some(rest) => rest.destructure(&collect_rest(values[items.len()..].to_vec()), scope, bindings),
+        None => Ok(Ok(())),
+    }
+}
@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use crate::parse::ast::BinaryOp;
+use crate::runtime::value::Value;
+use crate::runtime::{runtime_panic, RuntimeControlFlow};
+
+/// Applies a binary operator where at least one operand is a `Str` or `Char`.
+///
+/// Strings and chars can be concatenated with `+` and compared with each other,
+/// and repeated with `*` by a non-negative integer.
+pub fn execute_string_binop(op: BinaryOp, left: Value, right: Value) -> Result<Value, RuntimeControlFlow> {
+    match (op, left, right) {
+        (BinaryOp::Mul, text @ (Value::Str(_) | Value::Char(_)), count)
+        | (BinaryOp::Mul, count, text @ (Value::Str(_) | Value::Char(_)))
+            if count.as_i128().is_some() =>
+        {
+            repeat(&text, &count)
+        }
+        (op, left @ (Value::Str(_) | Value::Char(_)), right @ (Value::Str(_) | Value::Char(_))) => {
+            let ordering = match (&left, &right) {
+                (Value::Char(left), Value::Char(right)) => left.cmp(right),
+                _ => as_str(&left).cmp(&as_str(&right)),
+            };
+            Ok(match op {
+                BinaryOp::Add => {
+                    let mut out = as_str(&left).into_owned();
+                    out.push_str(&as_str(&right));
+                    Value::Str(out)
+                }
+                BinaryOp::Eq => Value::Bool(ordering == Ordering::Equal),
+                BinaryOp::Neq => Value::Bool(ordering != Ordering::Equal),
+                BinaryOp::Lt => Value::Bool(ordering == Ordering::Less),
+                BinaryOp::Lte => Value::Bool(ordering != Ordering::Greater),
+                BinaryOp::Gt => Value::Bool(ordering == Ordering::Greater),
+                BinaryOp::Gte => Value::Bool(ordering != Ordering::Less),
+                _ => runtime_panic!("strs can't do that"),
+            })
+        }
+        (_, left, right) => runtime_panic!("strs can't do that with {} and {}", left, right),
+    }
+}
+
+fn as_str(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::Str(x) => x.into(),
+        Value::Char(x) => x.to_string().into(),
+        _ => unreachable!("not a str or char"),
+    }
+}
+
+/// The longest string, in bytes, that repeating a string can make, so that
+/// scripts panic instead of aborting the host when it runs out of memory.
+pub const MAX_REPEAT_LEN: usize = 1 << 28;
+
+fn repeat(text: &Value, count: &Value) -> Result<Value, RuntimeControlFlow> {
+    let text = as_str(text);
+    // only called with integer counts
+    let count = count.as_i128().unwrap();
+    match usize::try_from(count) {
+        Ok(count) if text.len().checked_mul(count).is_some_and(|x| x <= MAX_REPEAT_LEN) => {
+            Ok(Value::Str(text.repeat(count)))
+        }
+        Ok(_) => runtime_panic!("attempt to repeat a string past {} bytes", MAX_REPEAT_LEN),
+        Err(_) => runtime_panic!("can't repeat a string {} times", count),
+    }
+}
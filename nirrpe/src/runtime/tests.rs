@@ -0,0 +1,351 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use crate::parse::ast::{BinaryOp, Decl, Expr, ExprKind, FnDecl, Lit, Modifiers, NumType, Program, Stmt, StmtKind};
+use crate::parse::ident::Ident;
+use crate::runtime::modules::{DEFAULT_MAX_CALL_DEPTH, MAX_IMPORT_DEPTH};
+use crate::runtime::natives::Natives;
+use crate::runtime::strings::{execute_string_binop, MAX_REPEAT_LEN};
+use crate::runtime::value::Value;
+use crate::runtime::{NirrpeRuntime, RuntimeControlFlow, Scope};
+use crate::{Engine, FileLoader, ModuleLoader, Runtime, RuntimeError, Script};
+
+/// Wraps hand-built statements in a script with no source text.
+fn script(stmts: Vec<Stmt>) -> Script {
+    Script {
+        path: PathBuf::from("test.nir"),
+        source: String::new(),
+        program: Program {
+            docs: Vec::new(),
+            stmts,
+        },
+        loader: Rc::new(FileLoader::new(".")),
+    }
+}
+
+/// Compiles and runs a script, returning the message it panicked with.
+fn panic_message(src: &str) -> String {
+    let script = Engine::new().compile(src, "test.nir").expect("script should compile");
+    match script.run(&mut Runtime::new()) {
+        Ok(value) => panic!("script should have panicked, but returned {}", value),
+        Err(panic) => panic.value.to_string(),
+    }
+}
+
+/// Loads modules from a fixed set of sources.
+struct MemoryLoader(HashMap<String, String>);
+
+impl ModuleLoader for MemoryLoader {
+    fn resolve(&self, _: &Path, path: &str) -> Result<PathBuf, String> {
+        Ok(PathBuf::from(path))
+    }
+
+    fn load(&self, path: &Path) -> Result<String, String> {
+        match self.0.get(path.to_str().unwrap_or_default()) {
+            Some(x) => Ok(x.clone()),
+            None => Err(format!("no module {}", path.display())),
+        }
+    }
+}
+
+/// Compiles and runs a script that imports from `modules`.
+fn run_with_modules(src: &str, modules: &[(&str, &str)]) -> Result<Value, RuntimeError> {
+    let modules = modules.iter().map(|(path, src)| (path.to_string(), src.to_string()));
+    Engine::with_loader(MemoryLoader(modules.collect()))
+        .compile(src, "main.nir")
+        .expect("script should compile")
+        .run(&mut Runtime::new())
+}
+
+fn function(name: &str) -> FnDecl {
+    FnDecl {
+        span: (0..0).into(),
+        docs: Vec::new(),
+        modifiers: Modifiers::empty(),
+        name: Ident::new(name),
+        args: Vec::new(),
+        return_ty: None,
+        body: Some(Vec::new()),
+    }
+}
+
+#[test]
+fn dropping_a_runtime_frees_its_modules() {
+    let mut runtime = NirrpeRuntime::new();
+    runtime.execute(&script(Vec::new())).unwrap();
+    let cache = Rc::downgrade(&runtime.modules);
+    drop(runtime);
+    assert!(cache.upgrade().is_none());
+}
+
+#[test]
+fn main_module_items_can_be_read_after_it_panics() {
+    let decl = Stmt::new(StmtKind::Decl(Decl::FnDecl(function("item"))), (0..0).into());
+    let error = Stmt::from(Expr::new(ExprKind::Error, (0..0).into()));
+    let mut runtime = NirrpeRuntime::new();
+    runtime.set_global("global", Value::Bool(true));
+    assert!(runtime.get("item").is_none());
+    runtime.execute(&script(vec![decl, error])).unwrap_err();
+    assert!(matches!(runtime.get("item"), Some(Value::Function(_))));
+    assert!(matches!(runtime.get("global"), Some(Value::Bool(true))));
+    assert!(runtime.get_global("item").is_none());
+}
+
+/// Defines `counter`, which returns a closure over the scope of its call,
+/// and passes that closure to the native `keep` before returning it.
+const COUNTER: &str = "extern impure fn keep(x: any)
+impure fn counter() {
+    let count = 0
+    let next = fn() {
+        count += 1
+        count
+    }
+    keep(next)
+    next
+}
+";
+
+/// Runs a script defining [`COUNTER`], returning the runtime along with the
+/// scope of every call to `counter`.
+fn run_counters(src: &str) -> (Runtime, Vec<Weak<Scope>>) {
+    let scopes = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = Runtime::new();
+    let kept = scopes.clone();
+    runtime.register_variadic("keep", move |args| {
+        if let Some(Value::Function(function)) = args.first() {
+            kept.borrow_mut().push(Rc::downgrade(function.env()));
+        }
+        Ok(Value::unit())
+    });
+    let script = Engine::new()
+        .compile(&format!("{}{}", COUNTER, src), "test.nir")
+        .expect("script should compile");
+    script.run(&mut runtime).unwrap();
+    let scopes = scopes.take();
+    (runtime, scopes)
+}
+
+#[test]
+fn returned_closures_keep_their_scope_alive_until_the_runtime_is_dropped() {
+    let (runtime, scopes) = run_counters("let next = counter()\nnext()\nnext()");
+    let Some(Value::Function(next)) = runtime.get("next") else {
+        panic!("next should be a function");
+    };
+    assert!(scopes[0].upgrade().is_some());
+    drop(next);
+    drop(runtime);
+    assert!(scopes[0].upgrade().is_none());
+}
+
+#[test]
+fn unreachable_closures_are_collected_after_running() {
+    let (_runtime, scopes) = run_counters("counter()\n[counter()]\n[{ next: counter() }]\nlet kept = counter()\n()");
+    assert_eq!(scopes.len(), 4);
+    assert!(scopes[..3].iter().all(|x| x.upgrade().is_none()));
+    assert!(scopes[3].upgrade().is_some());
+}
+
+#[test]
+fn values_the_host_holds_survive_the_runtime() {
+    let (runtime, scopes) = run_counters("let next = counter()");
+    let next = runtime.get("next").unwrap();
+    drop(runtime);
+    assert!(scopes[0].upgrade().is_some());
+    drop(next);
+    assert!(scopes[0].upgrade().is_none());
+}
+
+#[test]
+fn executing_an_error_node_panics() {
+    let error = Stmt::from(Expr::new(ExprKind::Error, (0..0).into()));
+    let panic = NirrpeRuntime::new().execute(&script(vec![error])).unwrap_err();
+    assert_eq!(panic.value.to_string(), "Cannot execute AST with errors!");
+}
+
+#[test]
+fn unsupported_unary_op_panics() {
+    assert_eq!(panic_message("-\"text\""), "can't apply Minus to text");
+}
+
+#[test]
+fn unsupported_binary_op_panics() {
+    assert_eq!(panic_message("true + 1"), "can't apply Add to true and 1");
+}
+
+#[test]
+fn assigning_to_an_undefined_variable_panics() {
+    assert_eq!(
+        panic_message("undefined = 1"),
+        "variable Ident(\"undefined\") is undefined"
+    );
+}
+
+#[test]
+fn missing_print_argument_panics() {
+    assert_eq!(
+        panic_message("extern impure fn print(x: any)\nprint()"),
+        "missing argument Ident(\"x\") to function Ident(\"print\")"
+    );
+}
+
+#[test]
+fn unsigned_underflow_panics() {
+    assert_eq!(panic_message("0u64 - 1u64"), "attempt to subtract with overflow");
+}
+
+#[test]
+fn division_by_zero_panics() {
+    assert_eq!(panic_message("1 / 0"), "attempt to divide by zero");
+    assert_eq!(
+        panic_message("1 % 0"),
+        "attempt to calculate the remainder with a divisor of zero"
+    );
+}
+
+#[test]
+fn panics_keep_the_source_they_happened_in() {
+    let src = "fn divide() = 1 / 0\ndivide()";
+    let script = Engine::new().compile(src, "test.nir").expect("script should compile");
+    let panic = script.run(&mut Runtime::new()).unwrap_err();
+    assert_eq!(&*panic.location.expect("panic should have a location").source, src);
+    assert!(panic.trace.iter().all(|x| &*x.location.source == src));
+}
+
+#[test]
+fn import_cycles_panic() {
+    let modules = [("a.nir", "import \"b.nir\""), ("b.nir", "import \"a.nir\"")];
+    let panic = run_with_modules("import \"a.nir\"", &modules).unwrap_err();
+    assert_eq!(
+        panic.value.to_string(),
+        "import cycle detected: a.nir -> b.nir -> a.nir"
+    );
+}
+
+#[test]
+fn only_pub_items_can_be_used_from_other_modules() {
+    let modules = [("items.nir", "pub fn public() = 1u8\nfn private() = 2u8")];
+    let value = run_with_modules("import \"items.nir\"\nuse items.public as public\npublic()", &modules);
+    assert!(matches!(value, Ok(Value::U8(1))));
+    let panic = run_with_modules("import \"items.nir\"\nuse items.private as private", &modules).unwrap_err();
+    assert!(panic.value.to_string().contains("is private to module items.nir"));
+}
+
+#[test]
+fn file_loaders_refuse_modules_outside_their_root() {
+    let root = std::env::temp_dir().join("nirrpe-loader-test");
+    std::fs::create_dir_all(root.join("scripts")).unwrap();
+    std::fs::write(root.join("scripts/inside.nir"), "").unwrap();
+    std::fs::write(root.join("outside.nir"), "").unwrap();
+    let loader = FileLoader::new(root.join("scripts"));
+    let main = root.join("scripts/main.nir");
+    assert!(loader.resolve(&main, "inside.nir").is_ok());
+    assert!(loader
+        .resolve(&main, "../outside.nir")
+        .unwrap_err()
+        .contains("is outside of the module root"));
+}
+
+#[test]
+fn deeply_nested_imports_panic() {
+    let modules = (0..=MAX_IMPORT_DEPTH)
+        .map(|i| (format!("{}.nir", i), format!("import \"{}.nir\"", i + 1)))
+        .collect::<Vec<_>>();
+    let modules = modules
+        .iter()
+        .map(|(x, y)| (x.as_str(), y.as_str()))
+        .collect::<Vec<_>>();
+    let panic = run_with_modules("import \"0.nir\"", &modules).unwrap_err();
+    assert_eq!(
+        panic.value.to_string(),
+        format!("imports nested more than {} deep", MAX_IMPORT_DEPTH)
+    );
+}
+
+#[test]
+fn recursion_can_reach_the_default_call_depth() {
+    let src = "fn count(n: u64): u64 = if n == 0 { 0 } else { 1 + count(n - 1) }\ncount(999)";
+    let script = Engine::new().compile(src, "test.nir").expect("script should compile");
+    assert_eq!(DEFAULT_MAX_CALL_DEPTH, 1000);
+    assert!(matches!(script.run(&mut Runtime::new()), Ok(Value::U64(999))));
+}
+
+#[test]
+fn runaway_recursion_panics() {
+    assert_eq!(panic_message("fn forever() = forever()\nforever()"), "stack overflow");
+}
+
+#[test]
+fn repeating_a_string_past_the_limit_panics() {
+    let repeat = |count| execute_string_binop(BinaryOp::Mul, Value::Str("xy".to_string()), Value::U64(count));
+    assert!(matches!(repeat(3), Ok(Value::Str(x)) if x == "xyxyxy"));
+    for count in [MAX_REPEAT_LEN as u64 / 2 + 1, 1_000_000_000_000_000_000] {
+        let Err(RuntimeControlFlow::Panic(panic)) = repeat(count) else {
+            panic!("repeating a string {} times should panic", count);
+        };
+        assert_eq!(
+            panic.value.to_string(),
+            format!("attempt to repeat a string past {} bytes", MAX_REPEAT_LEN)
+        );
+    }
+}
+
+#[test]
+fn literals_are_checked_against_their_type() {
+    let execute = |x, ty| {
+        let lit = Expr::new(ExprKind::Lit(Lit::Int(x, ty)), (0..0).into());
+        NirrpeRuntime::new().execute(&script(vec![Stmt::from(lit)]))
+    };
+    assert!(matches!(execute(-128, Some(NumType::I8)), Ok(Value::I8(i8::MIN))));
+    assert!(matches!(execute(-1, None), Ok(Value::I64(-1))));
+    assert!(matches!(execute(u64::MAX as i128, None), Ok(Value::U64(u64::MAX))));
+    assert_eq!(
+        execute(128, Some(NumType::I8)).unwrap_err().value.to_string(),
+        "literal `128' out of range for i8"
+    );
+}
+
+#[test]
+fn range_bounds_are_promoted_to_the_same_type() {
+    let execute = |start, end| {
+        let lit = |(x, ty)| Box::new(Expr::new(ExprKind::Lit(Lit::Int(x, ty)), (0..0).into()));
+        let range = ExprKind::Range {
+            start: lit(start),
+            end: lit(end),
+            inclusive: true,
+        };
+        NirrpeRuntime::new().execute(&script(vec![Stmt::from(Expr::new(range, (0..0).into()))]))
+    };
+    let range = execute((250, Some(NumType::U8)), (300, None)).unwrap();
+    let items = range.try_iter().unwrap().collect::<Vec<_>>();
+    assert_eq!(items.len(), 51);
+    assert!(matches!(items.last(), Some(Value::U64(300))));
+    assert_eq!(
+        execute((0, Some(NumType::I64)), (u64::MAX as i128, None))
+            .unwrap_err()
+            .value
+            .to_string(),
+        "18446744073709551615 doesn't fit in i64"
+    );
+}
+
+#[test]
+fn natives_panic_with_the_errors_they_return() {
+    let mut natives = Natives::default();
+    natives.register_variadic("fail", |_| Err(RuntimeError::new(Value::Str("failed".to_string()))));
+    natives.register("half", |x: u8| x / 2);
+    let call = |name, args| {
+        let name = Ident::new(name);
+        match natives.get(&name).unwrap().call(&name, args) {
+            Ok(value) => panic!("{:?} should have panicked, but returned {}", name, value),
+            Err(RuntimeControlFlow::Panic(panic)) => panic.value.to_string(),
+            Err(_) => panic!("{:?} should have panicked", name),
+        }
+    };
+    assert_eq!(call("fail", Vec::new()), "failed");
+    assert_eq!(
+        call("half", vec![Value::Str("x".to_string())]),
+        "expected an integer, found x"
+    );
+}
@@ -0,0 +1,91 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use chumsky::span::SimpleSpan;
+
+use crate::parse::ident::Ident;
+use crate::runtime::value::Value;
+use crate::runtime::Scope;
+
+/// A panic raised while running a program, along with where it happened.
+#[derive(Clone, Debug)]
+pub struct Panic {
+    pub value: Value,
+    /// The innermost expression or statement that panicked.
+    pub location: Option<Location>,
+    /// The Nirrpe function calls and imports that led to the panic,
+    /// innermost first.
+    pub trace: Vec<Frame>,
+}
+
+impl Panic {
+    pub fn new(value: Value) -> Self {
+        Self {
+            value,
+            location: None,
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl Display for Panic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Program panicked at '{}'", self.value)?;
+        if let Some(location) = &self.location {
+            write!(f, " in {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Panic {}
+
+#[derive(Clone)]
+pub struct Location {
+    /// The module the span is in.
+    pub path: PathBuf,
+    /// The text of that module.
+    pub source: Rc<str>,
+    pub span: SimpleSpan,
+}
+
+impl Location {
+    pub fn new(span: SimpleSpan, scope: &Scope) -> Self {
+        let info = scope.module_info();
+        Self {
+            path: info.map(|x| x.path.clone()).unwrap_or_default(),
+            source: info.map(|x| x.source.clone()).unwrap_or_else(|| "".into()),
+            span,
+        }
+    }
+}
+
+impl Debug for Location {
+    // the source is usually far too long to be worth printing
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Location")
+            .field("path", &self.path)
+            .field("span", &self.span)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}..{}", self.path.display(), self.span.start, self.span.end)
+    }
+}
+
+/// A call to a function or an import of a module, located where it was made.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub location: Location,
+}
+
+#[derive(Clone, Debug)]
+pub enum FrameKind {
+    Call(Ident),
+    Import(String),
+}
@@ -1,13 +1,15 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::Deref;
 use std::rc::Rc;
 
-use crate::parse::ast::{FnDecl, Lit};
+use crate::parse::ast::{FnDecl, Lit, NumType};
 use crate::parse::ident::Ident;
+use crate::runtime::modules::Module;
 use crate::runtime::utils::DelegateDebugToDisplay;
-use crate::runtime::{runtime_panic, RuntimeControlFlow};
+use crate::runtime::{runtime_panic, RuntimeControlFlow, Scope};
 
 #[derive(Clone, Debug)]
 pub enum Value {
@@ -27,7 +29,9 @@ pub enum Value {
     Object(Rc<RefCell<Object>>),
     Array(Vec<Value>),
     Tuple(Vec<Value>),
-    Function(FnDecl),
+    Range(Box<Range>),
+    Function(Rc<Function>),
+    Module(Rc<Module>),
 }
 
 impl Value {
@@ -35,9 +39,10 @@ impl Value {
         Self::Tuple(Vec::new())
     }
 
-    /// Gets a property on an Object.
+    /// Gets a property on an Object, or a `pub` item of a Module.
     pub fn try_get_property(&self, prop: &Ident) -> Result<Value, RuntimeControlFlow> {
         match self {
+            Value::Module(module) => module.get(prop),
             Value::Object(object) => match object.borrow().values.get(&prop.id) {
                 Some(x) => Ok(x.clone()),
                 None => runtime_panic!("property {:?} not found in object", prop),
@@ -45,18 +50,117 @@ impl Value {
             _ => runtime_panic!("only objects can have properties"),
         }
     }
+
+    /// Gets an element of an Array or Tuple, or a property of an Object by name.
+    pub fn try_get_index(&self, index: &Value) -> Result<Value, RuntimeControlFlow> {
+        match (self, index) {
+            (Value::Array(items) | Value::Tuple(items), index) => Ok(items[checked_index(index, items.len())?].clone()),
+            (Value::Object(object), Value::Str(name)) => match object.borrow().values.get(name) {
+                Some(x) => Ok(x.clone()),
+                None => runtime_panic!("property {:?} not found in object", name),
+            },
+            (Value::Object(_), _) => runtime_panic!("object properties must be indexed by string"),
+            _ => runtime_panic!("only arrays, tuples and objects can be indexed"),
+        }
+    }
+
+    /// Converts an integer value to an `i128`, which can hold every integer type losslessly.
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Value::I8(x) => Some(x as i128),
+            Value::U8(x) => Some(x as i128),
+            Value::I16(x) => Some(x as i128),
+            Value::U16(x) => Some(x as i128),
+            Value::I32(x) => Some(x as i128),
+            Value::U32(x) => Some(x as i128),
+            Value::I64(x) => Some(x as i128),
+            Value::U64(x) => Some(x as i128),
+            _ => None,
+        }
+    }
+
+    /// Converts a numeric value to an `f64`, possibly losing precision.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F32(x) => Some(x as f64),
+            Value::F64(x) => Some(x),
+            _ => self.as_i128().map(|x| x as f64),
+        }
+    }
+
+    /// Compares two values of the same primitive kind, or two numbers of any type.
+    /// Returns `None` if the values can't be compared.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+            (Value::Char(x), Value::Char(y)) => Some(x.cmp(y)),
+            (Value::Str(x), Value::Str(y)) => Some(x.cmp(y)),
+            (Value::Tuple(x), Value::Tuple(y)) if x.is_empty() && y.is_empty() => Some(Ordering::Equal),
+            _ => match (self.as_i128(), other.as_i128()) {
+                (Some(x), Some(y)) => Some(x.cmp(&y)),
+                _ => self.as_f64()?.partial_cmp(&other.as_f64()?),
+            },
+        }
+    }
+
+    /// Creates an integer value of the same type as this one,
+    /// or `None` if `x` doesn't fit in that type.
+    pub fn with_same_int_type(&self, x: i128) -> Option<Value> {
+        NumType::of(self)?.int_value(x)
+    }
+
+    /// Gets an iterator over the elements of an iterable value.
+    ///
+    /// Strings are iterated by `char`, and objects by property name in sorted order.
+    pub fn try_iter(&self) -> Result<Box<dyn Iterator<Item = Value>>, RuntimeControlFlow> {
+        Ok(match self {
+            Value::Array(x) | Value::Tuple(x) => Box::new(x.clone().into_iter()),
+            Value::Str(x) => Box::new(x.chars().collect::<Vec<_>>().into_iter().map(Value::Char)),
+            Value::Object(object) => {
+                let mut keys = object.borrow().values.keys().cloned().collect::<Vec<_>>();
+                keys.sort_unstable();
+                Box::new(keys.into_iter().map(Value::Str))
+            }
+            Value::Range(range) => {
+                // ranges can only be constructed with integer bounds of the same type
+                let start = range.start.as_i128().unwrap();
+                let end = range.end.as_i128().unwrap() + range.inclusive as i128;
+                let template = range.start.clone();
+                Box::new((start..end).map_while(move |x| template.with_same_int_type(x)))
+            }
+            x => runtime_panic!("{} is not iterable", x),
+        })
+    }
+}
+
+/// Checks that `index` is an integer within `0..len` and converts it to a `usize`.
+pub fn checked_index(index: &Value, len: usize) -> Result<usize, RuntimeControlFlow> {
+    match index.as_i128() {
+        Some(x) if (0..len as i128).contains(&x) => Ok(x as usize),
+        Some(x) => runtime_panic!("index {} out of bounds for length {}", x, len),
+        None => runtime_panic!("index must be an integer, found {}", index),
+    }
 }
 
-impl From<&Lit> for Value {
-    fn from(lit: &Lit) -> Self {
-        match lit {
+impl TryFrom<&Lit> for Value {
+    type Error = RuntimeControlFlow;
+
+    fn try_from(lit: &Lit) -> Result<Self, RuntimeControlFlow> {
+        Ok(match lit {
             Lit::Unit => Self::unit(),
             Lit::Bool(x) => Self::Bool(*x),
             Lit::Char(x) => Self::Char(*x),
-            Lit::Int(x) => Self::U64(*x),
-            Lit::Float(x) => Self::F64(*x),
+            Lit::Int(x, ty) => {
+                let ty = ty.unwrap_or(Lit::default_int_type(*x));
+                match ty.int_value(*x) {
+                    Some(value) => value,
+                    None => runtime_panic!("literal `{}' out of range for {}", x, ty.name()),
+                }
+            }
+            Lit::Float(x, Some(NumType::F32)) => Self::F32(*x as f32),
+            Lit::Float(x, _) => Self::F64(*x),
             Lit::Str(x) => Self::Str(x.clone()),
-        }
+        })
     }
 }
 
@@ -85,11 +189,60 @@ impl Display for Value {
                 }
                 tuple.finish()
             }
-            Value::Function(x) => write!(f, "[function {:?}]", x.name),
+            Value::Range(x) => Display::fmt(x, f),
+            Value::Function(x) => write!(f, "[function {:?}]", x.decl.name),
+            Value::Module(x) => write!(f, "[module {}]", x.path.display()),
         }
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Range {
+    pub start: Value,
+    pub end: Value,
+    pub inclusive: bool,
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.start,
+            if self.inclusive { "..=" } else { ".." },
+            self.end
+        )
+    }
+}
+
+/// A function, along with the environment it was defined in.
+pub struct Function {
+    pub decl: Rc<FnDecl>,
+    env: Rc<Scope>,
+}
+
+impl Function {
+    pub fn new(decl: FnDecl, scope: &Rc<Scope>) -> Self {
+        Self {
+            decl: Rc::new(decl),
+            env: scope.clone(),
+        }
+    }
+
+    pub fn env(&self) -> &Rc<Scope> {
+        &self.env
+    }
+}
+
+impl Debug for Function {
+    // the environment may contain the function itself
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("decl", &self.decl)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug)]
 pub struct Object {
     pub values: HashMap<String, Value>,
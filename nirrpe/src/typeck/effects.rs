@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use crate::parse::ast::{
+    Assignment, ControlFlow, Decl, Expr, ExprKind, FnDecl, ImportDecl, InterpolatedPart, Modifiers, ObjectPropName,
+    PathSegment, Pattern, Program, Stmt, StmtKind, UseDecl,
+};
+use crate::parse::ident::Ident;
+use crate::typeck::TypeError;
+
+/// Checks that functions marked `pure` don't have side effects, inferring
+/// whether unannotated functions are pure from their bodies.
+///
+/// A function is impure if it's marked `impure`, is an `extern` function that
+/// isn't marked `pure`, assigns to a variable from outside of it, mutates an
+/// object it didn't create, or calls an impure function or a function value
+/// that can't be resolved statically.
+pub fn check(program: &Program) -> Vec<TypeError> {
+    let mut checker = EffectChecker {
+        fns: Vec::new(),
+        scopes: vec![HashMap::new()],
+        current: Vec::new(),
+    };
+    checker.walk_stmts(&program.stmts);
+    checker.finish()
+}
+
+struct FnInfo<'p> {
+    decl: &'p FnDecl,
+    /// The side effects this function has on its own.
+    effects: Vec<String>,
+    callees: Vec<usize>,
+}
+
+#[derive(Copy, Clone)]
+enum Binding {
+    Function(usize),
+    /// A variable declared inside `depth` nested functions. `fresh` variables
+    /// are initialized with a new object, array or tuple that nothing else
+    /// can refer to yet.
+    Variable {
+        depth: usize,
+        fresh: bool,
+    },
+}
+
+struct EffectChecker<'p> {
+    fns: Vec<FnInfo<'p>>,
+    scopes: Vec<HashMap<Ident, Binding>>,
+    /// The functions being walked, innermost last.
+    current: Vec<usize>,
+}
+
+impl<'p> EffectChecker<'p> {
+    fn lookup(&self, name: &Ident) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|x| x.get(name)).copied()
+    }
+
+    fn define(&mut self, name: &Ident, binding: Binding) {
+        self.scopes.last_mut().unwrap().insert(name.clone(), binding);
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn effect(&mut self, effect: String) {
+        if let Some(&current) = self.current.last() {
+            self.fns[current].effects.push(effect);
+        }
+    }
+
+    fn register(&mut self, decl: &'p FnDecl) -> usize {
+        self.fns.push(FnInfo {
+            decl,
+            effects: Vec::new(),
+            callees: Vec::new(),
+        });
+        self.fns.len() - 1
+    }
+
+    fn walk_stmts(&mut self, stmts: &'p [Stmt]) {
+        // functions can be called before they're declared
+        let mut hoisted = Vec::new();
+        for stmt in stmts {
+            if let StmtKind::Decl(Decl::FnDecl(decl)) = &stmt.kind {
+                let index = self.register(decl);
+                self.define(&decl.name, Binding::Function(index));
+                hoisted.push(index);
+            }
+        }
+        let mut hoisted = hoisted.into_iter();
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Decl(Decl::FnDecl(_)) => self.walk_fn(hoisted.next().unwrap()),
+                _ => self.walk_stmt(stmt),
+            }
+        }
+    }
+
+    fn walk_fn(&mut self, index: usize) {
+        let decl = self.fns[index].decl;
+        self.current.push(index);
+        self.scoped(|this| {
+            let depth = this.current.len();
+            for arg in &decl.args {
+                if let Some(default) = &arg.default {
+                    this.walk_expr(default);
+                }
+                this.define(&arg.name, Binding::Variable { depth, fresh: false });
+            }
+            if let Some(body) = &decl.body {
+                this.walk_stmts(body);
+            }
+        });
+        self.current.pop();
+    }
+
+    fn walk_stmt(&mut self, stmt: &'p Stmt) {
+        match &stmt.kind {
+            StmtKind::Decl(Decl::LetDecl(r#let)) => {
+                self.walk_expr(&r#let.value);
+                let fresh = matches!(
+                    (&r#let.pattern, &r#let.value.kind),
+                    (
+                        Pattern::Binding(_),
+                        ExprKind::Object { .. } | ExprKind::Array { .. } | ExprKind::Tuple { .. }
+                    )
+                );
+                self.bind_pattern(&r#let.pattern, fresh);
+            }
+            // walked by `walk_stmts`
+            StmtKind::Decl(Decl::FnDecl(_)) => {}
+            StmtKind::Decl(Decl::Import(ImportDecl { name, .. }) | Decl::Use(UseDecl { name, .. })) => {
+                let depth = self.current.len();
+                self.define(name, Binding::Variable { depth, fresh: false });
+            }
+            StmtKind::Expr(expr) => self.walk_expr(expr),
+            StmtKind::Assignment(assignment) => self.walk_assignment(assignment),
+            StmtKind::ControlFlow(ControlFlow::Break(Some(x)) | ControlFlow::Return(Some(x))) => self.walk_expr(x),
+            StmtKind::ControlFlow(_) | StmtKind::Error => {}
+        }
+    }
+
+    fn walk_assignment(&mut self, assignment: &'p Assignment) {
+        self.walk_expr(&assignment.value);
+        for segment in &assignment.path {
+            if let PathSegment::Index(index) = segment {
+                self.walk_expr(index);
+            }
+        }
+        let target = &assignment.target.id;
+        match self.lookup(&assignment.target) {
+            Some(Binding::Variable { depth, .. }) if depth != self.current.len() => {
+                self.effect(format!("assigns to outer variable `{}'", target));
+            }
+            Some(Binding::Variable { fresh: false, .. }) if !assignment.path.is_empty() => {
+                self.effect(format!("mutates `{}', which may be shared", target));
+            }
+            Some(Binding::Variable { .. }) => {}
+            Some(Binding::Function(_)) | None => self.effect(format!("assigns to outer variable `{}'", target)),
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &'p Pattern, fresh: bool) {
+        let depth = self.current.len();
+        match pattern {
+            Pattern::Wildcard | Pattern::Lit(_) | Pattern::Range { .. } => {}
+            Pattern::Binding(name) => self.define(name, Binding::Variable { depth, fresh }),
+            Pattern::Object { props } => props.iter().for_each(|(_, x)| self.bind_pattern(x, false)),
+            Pattern::Array { items, rest } | Pattern::Tuple { items, rest } => {
+                items.iter().for_each(|x| self.bind_pattern(x, false));
+                if let Some(rest) = rest {
+                    // the rest of an array or tuple is always copied into a new one
+                    self.bind_pattern(rest, true);
+                }
+            }
+        }
+    }
+
+    fn walk_expr(&mut self, expr: &'p Expr) {
+        match &expr.kind {
+            ExprKind::Lit(_) | ExprKind::Var { .. } | ExprKind::Error => {}
+            ExprKind::Object { props } => {
+                for prop in props {
+                    if let ObjectPropName::Expr(name) = &prop.name {
+                        self.walk_expr(name);
+                    }
+                    self.walk_expr(&prop.value);
+                }
+            }
+            ExprKind::Array { items } | ExprKind::Tuple { items } => items.iter().for_each(|x| self.walk_expr(x)),
+            ExprKind::Interpolated { parts } => {
+                for part in parts {
+                    if let InterpolatedPart::Expr(x) = part {
+                        self.walk_expr(x);
+                    }
+                }
+            }
+            ExprKind::Dot { left, .. } => self.walk_expr(left),
+            ExprKind::Index { target, index } => {
+                self.walk_expr(target);
+                self.walk_expr(index);
+            }
+            ExprKind::UnaryOp { input, .. } => self.walk_expr(input),
+            ExprKind::BinaryOp { left, right, .. } => {
+                self.walk_expr(left);
+                self.walk_expr(right);
+            }
+            ExprKind::Call { target, args } => {
+                for arg in args {
+                    self.walk_expr(&arg.value);
+                }
+                match &target.kind {
+                    ExprKind::Var { name } => match self.lookup(name) {
+                        Some(Binding::Function(callee)) => {
+                            if let Some(&current) = self.current.last() {
+                                self.fns[current].callees.push(callee);
+                            }
+                        }
+                        Some(Binding::Variable { .. }) => {
+                            self.effect(format!("calls `{}', which may be impure", name.id));
+                        }
+                        None => self.effect(format!("calls unknown function `{}'", name.id)),
+                    },
+                    _ => {
+                        self.walk_expr(target);
+                        self.effect("calls a function value, which may be impure".to_string());
+                    }
+                }
+            }
+            ExprKind::Lambda { decl } => {
+                let index = self.register(decl);
+                self.walk_fn(index);
+            }
+            ExprKind::Block { body } | ExprKind::Loop { body } => self.scoped(|this| this.walk_stmts(body)),
+            ExprKind::If {
+                condition,
+                body,
+                r#else,
+            } => {
+                self.walk_expr(condition);
+                self.scoped(|this| this.walk_stmts(body));
+                if let Some(r#else) = r#else {
+                    self.scoped(|this| this.walk_expr(r#else));
+                }
+            }
+            ExprKind::While { condition, body } => {
+                self.walk_expr(condition);
+                self.scoped(|this| this.walk_stmts(body));
+            }
+            ExprKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                self.walk_expr(iterable);
+                self.scoped(|this| {
+                    let depth = this.current.len();
+                    this.define(binding, Binding::Variable { depth, fresh: false });
+                    this.walk_stmts(body);
+                });
+            }
+            ExprKind::Try { body, binding, handler } => {
+                self.scoped(|this| this.walk_stmts(body));
+                self.scoped(|this| {
+                    let depth = this.current.len();
+                    this.define(binding, Binding::Variable { depth, fresh: false });
+                    this.walk_stmts(handler);
+                });
+            }
+            ExprKind::Match { value, arms } => {
+                self.walk_expr(value);
+                for arm in arms {
+                    self.scoped(|this| {
+                        this.bind_pattern(&arm.pattern, false);
+                        if let Some(guard) = &arm.guard {
+                            this.walk_expr(guard);
+                        }
+                        this.walk_expr(&arm.body);
+                    });
+                }
+            }
+            ExprKind::Range { start, end, .. } => {
+                self.walk_expr(start);
+                self.walk_expr(end);
+            }
+        }
+    }
+
+    /// Infers which functions are impure, then reports the `pure` functions
+    /// that are.
+    fn finish(self) -> Vec<TypeError> {
+        let mut errors = Vec::new();
+
+        // functions marked `pure` are trusted by their callers, and checked below
+        let mut impure = self
+            .fns
+            .iter()
+            .map(|x| {
+                let modifiers = x.decl.modifiers;
+                !modifiers.contains(Modifiers::PURE)
+                    && (modifiers.contains(Modifiers::IMPURE)
+                        || (modifiers.contains(Modifiers::EXTERN) && x.decl.body.is_none())
+                        || !x.effects.is_empty())
+            })
+            .collect::<Vec<_>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, info) in self.fns.iter().enumerate() {
+                if !impure[i]
+                    && !info.decl.modifiers.contains(Modifiers::PURE)
+                    && info.callees.iter().any(|&x| impure[x])
+                {
+                    impure[i] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        for info in &self.fns {
+            let decl = info.decl;
+            if !decl.modifiers.contains(Modifiers::PURE) {
+                continue;
+            }
+            if decl.modifiers.contains(Modifiers::IMPURE) {
+                errors.push(TypeError {
+                    span: decl.span,
+                    message: format!("function `{}' can't be both pure and impure", decl.name.id),
+                });
+                continue;
+            }
+            let effect = info.effects.first().cloned().or_else(|| {
+                info.callees
+                    .iter()
+                    .find(|&&x| impure[x])
+                    .map(|&x| format!("calls impure function `{}'", self.fns[x].decl.name.id))
+            });
+            if let Some(effect) = effect {
+                errors.push(TypeError {
+                    span: decl.span,
+                    message: format!("pure function `{}' {}", decl.name.id, effect),
+                });
+            }
+        }
+        errors
+    }
+}
@@ -0,0 +1,573 @@
+pub mod effects;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+
+use chumsky::span::SimpleSpan;
+
+use crate::parse::ast::{
+    BinaryOp, CallArg, ControlFlow, Decl, Expr, ExprKind, FnDecl, InterpolatedPart, Lit, Modifiers, NumType,
+    ObjectPropName, PathSegment, Pattern, Program, Stmt, StmtKind, UnaryOp,
+};
+use crate::parse::ident::Ident;
+use crate::typeck::types::Type;
+
+/// A type error found before running a program.
+#[derive(Clone, Debug)]
+pub struct TypeError {
+    pub span: SimpleSpan,
+    pub message: String,
+}
+
+/// Checks the type annotations in a program, and that the values passed to
+/// and returned from functions match them wherever their types can be
+/// inferred. Anything that can't be checked here is checked at runtime.
+pub fn check(program: &Program) -> Vec<TypeError> {
+    let mut checker = TypeChecker {
+        scopes: vec![HashMap::new()],
+        reassigned: Vec::new(),
+        return_types: Vec::new(),
+        errors: Vec::new(),
+    };
+    checker.check_stmts(&program.stmts);
+    checker.errors
+}
+
+#[derive(Clone)]
+enum Binding<'p> {
+    Value(Type),
+    Function(&'p FnDecl),
+}
+
+struct TypeChecker<'p> {
+    scopes: Vec<HashMap<Ident, Binding<'p>>>,
+    /// The variables reassigned anywhere in each list of statements being
+    /// checked, innermost last.
+    reassigned: Vec<HashSet<&'p Ident>>,
+    /// The return type of each function being checked, innermost last.
+    return_types: Vec<(Type, &'p FnDecl)>,
+    errors: Vec<TypeError>,
+}
+
+impl<'p> TypeChecker<'p> {
+    fn error(&mut self, span: SimpleSpan, message: String) {
+        self.errors.push(TypeError { span, message });
+    }
+
+    fn lookup(&self, name: &Ident) -> Option<&Binding<'p>> {
+        self.scopes.iter().rev().find_map(|x| x.get(name))
+    }
+
+    fn define(&mut self, name: &Ident, binding: Binding<'p>) {
+        // a variable that's reassigned anywhere can hold anything, even before
+        // the assignment runs
+        let binding = match self.reassigned.last() {
+            Some(x) if x.contains(name) => Binding::Value(Type::Any),
+            _ => binding,
+        };
+        self.scopes.last_mut().unwrap().insert(name.clone(), binding);
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let out = f(self);
+        self.scopes.pop();
+        out
+    }
+
+    fn resolve(&mut self, name: &Ident, span: SimpleSpan) -> Type {
+        match Type::from_name(name) {
+            Some(x) => x,
+            None => {
+                self.error(span, format!("unknown type `{}'", name.id));
+                Type::Any
+            }
+        }
+    }
+
+    fn check_stmts(&mut self, stmts: &'p [Stmt]) -> Type {
+        let mut reassigned = HashSet::new();
+        collect_reassigned(stmts, &mut reassigned);
+        self.reassigned.push(reassigned);
+        let mut last = Type::Unit;
+        for stmt in stmts {
+            last = self.check_stmt(stmt);
+        }
+        self.reassigned.pop();
+        last
+    }
+
+    fn check_stmt(&mut self, stmt: &'p Stmt) -> Type {
+        match &stmt.kind {
+            StmtKind::Decl(Decl::LetDecl(r#let)) => {
+                let ty = self.check_expr(&r#let.value);
+                self.bind_pattern(&r#let.pattern, ty);
+                Type::Unit
+            }
+            StmtKind::Decl(Decl::FnDecl(decl)) => {
+                // natives are checked when a module is loaded, which only looks at its top level
+                if decl.body.is_none() && decl.modifiers.contains(Modifiers::EXTERN) && self.scopes.len() > 1 {
+                    self.error(
+                        decl.span,
+                        format!(
+                            "extern function `{}' must be declared at the top level of a module",
+                            decl.name.id
+                        ),
+                    );
+                }
+                // defined first so that the function can call itself
+                self.define(&decl.name, Binding::Function(decl));
+                self.check_fn(decl);
+                Type::Unit
+            }
+            StmtKind::Decl(Decl::Import(import)) => {
+                self.define(&import.name, Binding::Value(Type::Module));
+                Type::Unit
+            }
+            // modules are loaded at runtime, so the types of their items aren't known
+            StmtKind::Decl(Decl::Use(r#use)) => {
+                self.define(&r#use.name, Binding::Value(Type::Any));
+                Type::Unit
+            }
+            StmtKind::Expr(expr) => self.check_expr(expr),
+            // the target was already bound as `any` by `define`
+            StmtKind::Assignment(assignment) => {
+                self.check_expr(&assignment.value);
+                Type::Unit
+            }
+            StmtKind::ControlFlow(ControlFlow::Return(value)) => {
+                let ty = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::Unit,
+                };
+                if let Some(&(expected, decl)) = self.return_types.last() {
+                    self.check_return(expected, ty, decl);
+                }
+                Type::Any
+            }
+            StmtKind::ControlFlow(ControlFlow::Break(Some(value))) => {
+                self.check_expr(value);
+                Type::Any
+            }
+            StmtKind::ControlFlow(_) | StmtKind::Error => Type::Any,
+        }
+    }
+
+    fn check_fn(&mut self, decl: &'p FnDecl) {
+        let return_type = match &decl.return_ty {
+            Some(x) => self.resolve(x, decl.span),
+            None => Type::Any,
+        };
+        self.scoped(|this| {
+            for arg in &decl.args {
+                let ty = this.resolve(&arg.ty, arg.span);
+                if let Some(default) = &arg.default {
+                    let default_ty = this.check_expr(default);
+                    if !ty.accepts(default_ty) {
+                        this.error(
+                            arg.span,
+                            format!(
+                                "default value of argument `{}' is {}, expected {}",
+                                arg.name.id, default_ty, ty
+                            ),
+                        );
+                    }
+                }
+                let ty = if arg.variadic { Type::Array } else { ty };
+                this.define(&arg.name, Binding::Value(ty));
+            }
+            if let Some(body) = &decl.body {
+                this.return_types.push((return_type, decl));
+                let ty = this.check_stmts(body);
+                this.return_types.pop();
+                this.check_return(return_type, ty, decl);
+            }
+        });
+    }
+
+    fn check_return(&mut self, expected: Type, found: Type, decl: &FnDecl) {
+        if !expected.accepts(found) {
+            self.error(
+                decl.span,
+                format!(
+                    "function `{}' returns {}, but its return type is {}",
+                    decl.name.id, found, expected
+                ),
+            );
+        }
+    }
+
+    fn check_call(&mut self, decl: &FnDecl, args: &'p [CallArg]) {
+        let mut next_positional = 0;
+        for arg in args {
+            let ty = self.check_expr(&arg.value);
+            let param = match &arg.name {
+                Some(name) => decl.args.iter().find(|x| &x.name == name),
+                None => decl.args.get(next_positional),
+            };
+            // the runtime reports arguments that don't belong to any parameter
+            let Some(param) = param else {
+                continue;
+            };
+            if arg.name.is_none() && !param.variadic {
+                next_positional += 1;
+            }
+            let expected = Type::from_name(&param.ty).unwrap_or(Type::Any);
+            if !expected.accepts(ty) {
+                self.error(
+                    arg.span,
+                    format!(
+                        "expected {} for argument `{}' of `{}', found {}",
+                        expected, param.name.id, decl.name.id, ty
+                    ),
+                );
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &'p Pattern, ty: Type) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Lit(lit) => {
+                self.check_expr(lit);
+            }
+            Pattern::Range { start, end, .. } => {
+                self.check_expr(start);
+                self.check_expr(end);
+            }
+            Pattern::Binding(name) => self.define(name, Binding::Value(ty)),
+            Pattern::Object { props } => props.iter().for_each(|(_, x)| self.bind_pattern(x, Type::Any)),
+            Pattern::Array { items, rest } => {
+                items.iter().for_each(|x| self.bind_pattern(x, Type::Any));
+                if let Some(rest) = rest {
+                    self.bind_pattern(rest, Type::Array);
+                }
+            }
+            Pattern::Tuple { items, rest } => {
+                items.iter().for_each(|x| self.bind_pattern(x, Type::Any));
+                if let Some(rest) = rest {
+                    self.bind_pattern(rest, Type::Tuple);
+                }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &'p Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Lit(lit) => {
+                if let Lit::Int(x, ty) = lit {
+                    let ty = ty.unwrap_or(Lit::default_int_type(*x));
+                    if !ty.can_hold(*x) {
+                        self.error(expr.span, format!("literal `{}' out of range for {}", x, ty.name()));
+                    }
+                }
+                lit_type(lit)
+            }
+            ExprKind::Object { props } => {
+                for prop in props {
+                    self.check_expr(&prop.value);
+                }
+                Type::Object
+            }
+            ExprKind::Array { items } => {
+                for item in items {
+                    self.check_expr(item);
+                }
+                Type::Array
+            }
+            ExprKind::Tuple { items } => {
+                for item in items {
+                    self.check_expr(item);
+                }
+                Type::Tuple
+            }
+            ExprKind::Interpolated { parts } => {
+                for part in parts {
+                    if let InterpolatedPart::Expr(x) = part {
+                        self.check_expr(x);
+                    }
+                }
+                Type::Str
+            }
+            ExprKind::Var { name } => match self.lookup(name) {
+                Some(Binding::Value(x)) => *x,
+                Some(Binding::Function(_)) => Type::Function,
+                None => Type::Any,
+            },
+            ExprKind::Dot { left, .. } => {
+                self.check_expr(left);
+                Type::Any
+            }
+            ExprKind::Index { target, index } => {
+                self.check_expr(target);
+                self.check_expr(index);
+                Type::Any
+            }
+            ExprKind::UnaryOp { ops, input } => {
+                let mut ty = self.check_expr(input);
+                for op in ops.iter().rev() {
+                    ty = match (op, ty) {
+                        (UnaryOp::Not, Type::Bool) => Type::Bool,
+                        (UnaryOp::Plus | UnaryOp::BitNot, Type::Num(x)) => Type::Num(x),
+                        (UnaryOp::Minus, Type::Num(x)) if x.is_float() => Type::Num(x),
+                        (UnaryOp::Minus, Type::Num(x)) => Type::Num(NumType::int(x.bits(), true)),
+                        _ => Type::Any,
+                    };
+                }
+                ty
+            }
+            ExprKind::BinaryOp { op, left, right } => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                match (op, left, right) {
+                    (
+                        BinaryOp::Eq
+                        | BinaryOp::Neq
+                        | BinaryOp::Lt
+                        | BinaryOp::Lte
+                        | BinaryOp::Gt
+                        | BinaryOp::Gte
+                        | BinaryOp::And
+                        | BinaryOp::Or,
+                        _,
+                        _,
+                    ) => Type::Bool,
+                    (_, Type::Num(x), Type::Num(y)) => Type::Num(x.promote(y)),
+                    (BinaryOp::Add, Type::Str | Type::Char, Type::Str | Type::Char) => Type::Str,
+                    (BinaryOp::Mul, Type::Str | Type::Char, Type::Num(_)) => Type::Str,
+                    _ => Type::Any,
+                }
+            }
+            ExprKind::Call { target, args } => {
+                let function = match &target.kind {
+                    ExprKind::Var { name } => match self.lookup(name) {
+                        Some(Binding::Function(decl)) => Some(*decl),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                self.check_expr(target);
+                match function {
+                    Some(decl) => {
+                        self.check_call(decl, args);
+                        match &decl.return_ty {
+                            Some(x) => Type::from_name(x).unwrap_or(Type::Any),
+                            None => Type::Any,
+                        }
+                    }
+                    None => {
+                        for arg in args {
+                            self.check_expr(&arg.value);
+                        }
+                        Type::Any
+                    }
+                }
+            }
+            ExprKind::Lambda { decl } => {
+                self.check_fn(decl);
+                Type::Function
+            }
+            ExprKind::Block { body } => self.scoped(|this| this.check_stmts(body)),
+            ExprKind::If {
+                condition,
+                body,
+                r#else,
+            } => {
+                self.check_expr(condition);
+                let body = self.scoped(|this| this.check_stmts(body));
+                match r#else {
+                    Some(r#else) if self.scoped(|this| this.check_expr(r#else)) == body => body,
+                    _ => Type::Any,
+                }
+            }
+            ExprKind::Loop { body } => {
+                self.scoped(|this| this.check_stmts(body));
+                Type::Any
+            }
+            ExprKind::While { condition, body } => {
+                self.check_expr(condition);
+                self.scoped(|this| this.check_stmts(body));
+                Type::Any
+            }
+            ExprKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                self.check_expr(iterable);
+                self.scoped(|this| {
+                    this.define(binding, Binding::Value(Type::Any));
+                    this.check_stmts(body);
+                });
+                Type::Any
+            }
+            ExprKind::Match { value, arms } => {
+                self.check_expr(value);
+                let mut ty = None;
+                for arm in arms {
+                    let arm_ty = self.scoped(|this| {
+                        this.bind_pattern(&arm.pattern, Type::Any);
+                        if let Some(guard) = &arm.guard {
+                            this.check_expr(guard);
+                        }
+                        this.check_expr(&arm.body)
+                    });
+                    ty = match ty {
+                        None => Some(arm_ty),
+                        Some(x) if x == arm_ty => Some(x),
+                        Some(_) => Some(Type::Any),
+                    };
+                }
+                ty.unwrap_or(Type::Any)
+            }
+            ExprKind::Try { body, binding, handler } => {
+                let body = self.scoped(|this| this.check_stmts(body));
+                let handler = self.scoped(|this| {
+                    this.define(binding, Binding::Value(Type::Any));
+                    this.check_stmts(handler)
+                });
+                match body == handler {
+                    true => body,
+                    false => Type::Any,
+                }
+            }
+            ExprKind::Range { start, end, .. } => {
+                self.check_expr(start);
+                self.check_expr(end);
+                Type::Range
+            }
+            ExprKind::Error => Type::Any,
+        }
+    }
+}
+
+fn lit_type(lit: &Lit) -> Type {
+    match lit {
+        Lit::Unit => Type::Unit,
+        Lit::Bool(_) => Type::Bool,
+        Lit::Char(_) => Type::Char,
+        Lit::Int(x, ty) => Type::Num(ty.unwrap_or(Lit::default_int_type(*x))),
+        Lit::Float(_, ty) => Type::Num(ty.unwrap_or(NumType::F64)),
+        Lit::Str(_) => Type::Str,
+    }
+}
+
+/// Collects the variables that are reassigned anywhere in `stmts`, including
+/// in nested blocks and functions, so that they can be bound as `any` before
+/// the statements that use them are checked.
+fn collect_reassigned<'p>(stmts: &'p [Stmt], names: &mut HashSet<&'p Ident>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Decl(Decl::LetDecl(r#let)) => collect_reassigned_expr(&r#let.value, names),
+            StmtKind::Decl(Decl::FnDecl(decl)) => collect_reassigned_fn(decl, names),
+            StmtKind::Decl(Decl::Import(_) | Decl::Use(_)) => {}
+            StmtKind::Expr(expr) => collect_reassigned_expr(expr, names),
+            StmtKind::Assignment(assignment) => {
+                // assigning to a property or an index doesn't change the variable's type
+                if assignment.path.is_empty() {
+                    names.insert(&assignment.target);
+                }
+                for segment in &assignment.path {
+                    if let PathSegment::Index(index) = segment {
+                        collect_reassigned_expr(index, names);
+                    }
+                }
+                collect_reassigned_expr(&assignment.value, names);
+            }
+            StmtKind::ControlFlow(ControlFlow::Break(Some(x)) | ControlFlow::Return(Some(x))) => {
+                collect_reassigned_expr(x, names)
+            }
+            StmtKind::ControlFlow(_) | StmtKind::Error => {}
+        }
+    }
+}
+
+fn collect_reassigned_fn<'p>(decl: &'p FnDecl, names: &mut HashSet<&'p Ident>) {
+    for arg in &decl.args {
+        if let Some(default) = &arg.default {
+            collect_reassigned_expr(default, names);
+        }
+    }
+    if let Some(body) = &decl.body {
+        collect_reassigned(body, names);
+    }
+}
+
+fn collect_reassigned_expr<'p>(expr: &'p Expr, names: &mut HashSet<&'p Ident>) {
+    match &expr.kind {
+        ExprKind::Lit(_) | ExprKind::Var { .. } | ExprKind::Error => {}
+        ExprKind::Object { props } => {
+            for prop in props {
+                if let ObjectPropName::Expr(name) = &prop.name {
+                    collect_reassigned_expr(name, names);
+                }
+                collect_reassigned_expr(&prop.value, names);
+            }
+        }
+        ExprKind::Array { items } | ExprKind::Tuple { items } => {
+            items.iter().for_each(|x| collect_reassigned_expr(x, names))
+        }
+        ExprKind::Interpolated { parts } => {
+            for part in parts {
+                if let InterpolatedPart::Expr(x) = part {
+                    collect_reassigned_expr(x, names);
+                }
+            }
+        }
+        ExprKind::Dot { left: input, .. } | ExprKind::UnaryOp { input, .. } => collect_reassigned_expr(input, names),
+        ExprKind::Index {
+            target: left,
+            index: right,
+        }
+        | ExprKind::BinaryOp { left, right, .. }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            collect_reassigned_expr(left, names);
+            collect_reassigned_expr(right, names);
+        }
+        ExprKind::Call { target, args } => {
+            collect_reassigned_expr(target, names);
+            args.iter().for_each(|x| collect_reassigned_expr(&x.value, names));
+        }
+        ExprKind::Lambda { decl } => collect_reassigned_fn(decl, names),
+        ExprKind::Block { body } | ExprKind::Loop { body } => collect_reassigned(body, names),
+        ExprKind::If {
+            condition,
+            body,
+            r#else,
+        } => {
+            collect_reassigned_expr(condition, names);
+            collect_reassigned(body, names);
+            if let Some(r#else) = r#else {
+                collect_reassigned_expr(r#else, names);
+            }
+        }
+        ExprKind::While { condition, body } => {
+            collect_reassigned_expr(condition, names);
+            collect_reassigned(body, names);
+        }
+        ExprKind::For { iterable, body, .. } => {
+            collect_reassigned_expr(iterable, names);
+            collect_reassigned(body, names);
+        }
+        ExprKind::Match { value, arms } => {
+            collect_reassigned_expr(value, names);
+            for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    collect_reassigned_expr(guard, names);
+                }
+                collect_reassigned_expr(&arm.body, names);
+            }
+        }
+        ExprKind::Try { body, handler, .. } => {
+            collect_reassigned(body, names);
+            collect_reassigned(handler, names);
+        }
+    }
+}
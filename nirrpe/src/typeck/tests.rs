@@ -0,0 +1,85 @@
+use crate::Engine;
+
+fn check(src: &str) -> Result<(), String> {
+    Engine::new()
+        .compile(src, "test.nir")
+        .map(|_| ())
+        .map_err(|x| x.to_string())
+}
+
+#[test]
+fn arguments_of_the_wrong_type_are_rejected() {
+    assert!(check("fn takes_str(s: str) = s\nlet x = 1\ntakes_str(x)").is_err());
+}
+
+#[test]
+fn variables_reassigned_after_use_arent_checked() {
+    check("fn takes_str(s: str) = s\nlet x = 1\nlet f = fn() = takes_str(x)\nx = \"s\"\nf()").unwrap();
+}
+
+#[test]
+fn variables_reassigned_later_in_a_loop_arent_checked() {
+    check("fn takes_str(s: str) = s\nlet x = 1\nloop {\n    if x == \"s\" {\n        takes_str(x)\n        break\n    }\n    x = \"s\"\n}")
+        .unwrap();
+}
+
+#[test]
+fn literals_are_only_one_past_the_largest_signed_integer_when_negated() {
+    check("-128i8").unwrap();
+    check("-9223372036854775808i64").unwrap();
+    check("-9223372036854775808").unwrap();
+    check("128i8").unwrap_err();
+    check("-129i8").unwrap_err();
+    check("-9223372036854775809").unwrap_err();
+    check("-1u8").unwrap_err();
+}
+
+#[test]
+fn pure_functions_with_side_effects_are_rejected() {
+    let err = check("let x = 0\npure fn bump() {\n    x = 1\n}").unwrap_err();
+    assert!(
+        err.contains("pure function `bump' assigns to outer variable `x'"),
+        "{}",
+        err
+    );
+    let err = check("pure fn heal(target: any) {\n    target.hp = 10\n}").unwrap_err();
+    assert!(err.contains("pure function `heal' mutates `target'"), "{}", err);
+    let err = check("extern fn log(x: any)\npure fn greet() = log(\"hi\")").unwrap_err();
+    assert!(
+        err.contains("pure function `greet' calls impure function `log'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn effects_propagate_through_calls() {
+    let err = check(
+        "extern impure fn log(x: any)\nfn greet() = log(\"hi\")\nfn welcome() = greet()\npure fn enter() = welcome()",
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("pure function `enter' calls impure function `welcome'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn declared_effects_are_accepted() {
+    check(
+        "extern impure fn log(x: any)
+        extern pure fn roll(sides: u64)
+        impure fn greet() = log(\"hi\")
+        fn welcome() = greet()
+        pure fn heal(hp: u64) = hp + 1
+        pure fn heal_twice(hp: u64) = heal(heal(hp))
+        pure fn dice() = roll(6)
+        pure fn hero() {
+            let hero = { hp: 1 }
+            hero.hp = heal(hero.hp)
+            hero
+        }",
+    )
+    .unwrap();
+}
@@ -0,0 +1,89 @@
+use std::fmt::{Display, Formatter};
+
+use crate::parse::ast::NumType;
+use crate::parse::ident::Ident;
+use crate::runtime::value::Value;
+
+/// The type of a value, as written in argument and return type annotations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Unit,
+    Bool,
+    Char,
+    Str,
+    Num(NumType),
+    Object,
+    Array,
+    Tuple,
+    Range,
+    Function,
+    Module,
+}
+
+impl Type {
+    /// Resolves a type name, or returns `None` if there's no such type.
+    pub fn from_name(name: &Ident) -> Option<Self> {
+        Some(match name.id.as_str() {
+            "any" => Self::Any,
+            "unit" => Self::Unit,
+            "bool" => Self::Bool,
+            "char" => Self::Char,
+            "str" => Self::Str,
+            "object" => Self::Object,
+            "array" => Self::Array,
+            "tuple" => Self::Tuple,
+            "range" => Self::Range,
+            "fn" => Self::Function,
+            "module" => Self::Module,
+            x => Self::Num(NumType::from_name(x)?),
+        })
+    }
+
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => Self::Bool,
+            Value::Char(_) => Self::Char,
+            Value::Str(_) => Self::Str,
+            Value::Object(_) => Self::Object,
+            Value::Array(_) => Self::Array,
+            Value::Tuple(x) if x.is_empty() => Self::Unit,
+            Value::Tuple(_) => Self::Tuple,
+            Value::Range(_) => Self::Range,
+            Value::Function(_) => Self::Function,
+            Value::Module(_) => Self::Module,
+            x => Self::Num(NumType::of(x).unwrap()),
+        }
+    }
+
+    /// Whether a value of type `other` can be passed where this type is
+    /// expected. Numbers are converted between types, except that floats are
+    /// never implicitly converted to integers.
+    pub fn accepts(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Any, _) | (_, Self::Any) => true,
+            (Self::Num(x), Self::Num(y)) => x.is_float() || !y.is_float(),
+            (Self::Tuple, Self::Unit) => true,
+            (x, y) => x == y,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Type::Any => "any",
+            Type::Unit => "unit",
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::Str => "str",
+            Type::Num(x) => x.name(),
+            Type::Object => "object",
+            Type::Array => "array",
+            Type::Tuple => "tuple",
+            Type::Range => "range",
+            Type::Function => "fn",
+            Type::Module => "module",
+        })
+    }
+}